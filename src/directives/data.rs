@@ -0,0 +1,216 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+use serde_json::{self, Value};
+use toml;
+use directives::DirectiveHandler;
+use evaluator::{StoredValue, Worker};
+use parse::Node;
+
+/// Render a data value the way it should appear when spliced into a document.
+pub fn stringify(value: &Value) -> String {
+    match *value {
+        Value::Null => "".to_owned(),
+        Value::String(ref s) => s.to_owned(),
+        Value::Bool(b) => if b { "true".to_owned() } else { "".to_owned() },
+        Value::Number(ref n) => n.to_string(),
+        Value::Array(_) | Value::Object(_) => value.to_string(),
+    }
+}
+
+fn load(path: &Path) -> Result<Value, String> {
+    let mut data = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut data))
+        .map_err(|err| err.to_string())?;
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str(&data).map_err(|err| err.to_string()),
+        Some("toml") => {
+            let value: toml::Value = toml::from_str(&data).map_err(|err| err.to_string())?;
+            serde_json::to_value(value).map_err(|err| err.to_string())
+        }
+        _ => Err("Unknown data format: expected a .json or .toml file".to_owned()),
+    }
+}
+
+pub struct LoadData;
+
+impl DirectiveHandler for LoadData {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        if args.len() != 2 {
+            return Err(());
+        }
+
+        let name = worker.evaluate(&args[0]);
+        let path = worker.evaluate(&args[1]);
+        let path = worker.get_source_path(&args[1], &path);
+        let value = match load(&path) {
+            Ok(v) => v,
+            Err(msg) => {
+                let msg = format!("Failed to load '{}': {}", path.to_string_lossy(), msg);
+                worker.error(&args[1], &msg);
+                return Err(());
+            }
+        };
+
+        worker.ctx.insert(name, Arc::new(StoredValue::Data(value)));
+        Ok("".to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use directives::ThemeConfig;
+    use evaluator::Evaluator;
+    use page::UrlConfig;
+    use parse::Node;
+
+    fn node_string(s: &str) -> Node {
+        Node::new_string(s, 0, -1)
+    }
+
+    fn node_children(nodes: Vec<Node>) -> Node {
+        Node::new_children(nodes, 0, -1)
+    }
+
+    #[test]
+    fn test_lookup_data() {
+        let evaluator = Evaluator::new();
+        let mut worker = Worker::new(&evaluator);
+        let data = json!({
+            "server": {"latest": "3.6.1", "supported": ["3.4", "3.6"]},
+            "count": 2,
+        });
+        worker
+            .ctx
+            .insert("versions".to_owned(), Arc::new(StoredValue::Data(data)));
+
+        assert_eq!(
+            worker.lookup(&node_string(""), "versions.server.latest", &[]),
+            Ok("3.6.1".to_owned())
+        );
+        assert_eq!(
+            worker.lookup(&node_string(""), "versions.server.supported.1", &[]),
+            Ok("3.6".to_owned())
+        );
+        assert_eq!(
+            worker.lookup(&node_string(""), "versions.count", &[]),
+            Ok("2".to_owned())
+        );
+        assert!(
+            worker
+                .lookup(&node_string(""), "versions.server.missing", &[])
+                .is_err()
+        );
+
+        let handler = ThemeConfig;
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[
+                    node_string("supported"),
+                    node_children(vec![node_string("versions.server.supported")]),
+                ]
+            ),
+            Ok("".to_owned())
+        );
+        assert_eq!(
+            worker.theme_config.get("supported"),
+            Some(&json!(["3.4", "3.6"]))
+        );
+    }
+
+    fn test_evaluator() -> Evaluator {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test");
+        Evaluator::new_with_options(root, UrlConfig::default())
+    }
+
+    #[test]
+    fn test_load_data() {
+        let evaluator = test_evaluator();
+        let mut worker = Worker::new(&evaluator);
+        let handler = LoadData;
+
+        for (name, path) in &[("json", "/data/versions.json"), ("toml", "/data/versions.toml")] {
+            assert_eq!(
+                handler.handle(&mut worker, &[node_string(name), node_string(path)]),
+                Ok("".to_owned())
+            );
+            assert_eq!(
+                worker.lookup(&node_string(""), &format!("{}.server.latest", name), &[]),
+                Ok("3.6.1".to_owned())
+            );
+            assert_eq!(
+                worker.lookup_data(&format!("{}.server.supported", name)),
+                Some(json!(["3.4", "3.6"]))
+            );
+        }
+
+        assert_eq!(evaluator.get_num_errors(), 0);
+    }
+
+    #[test]
+    fn test_load_data_errors() {
+        let evaluator = test_evaluator();
+        let mut worker = Worker::new(&evaluator);
+        let handler = LoadData;
+
+        assert!(handler.handle(&mut worker, &[node_string("x")]).is_err());
+
+        let paths = ["/data/missing.json", "/data/malformed.json", "/data/versions.yaml"];
+        for (i, path) in paths.iter().enumerate() {
+            assert!(
+                handler
+                    .handle(&mut worker, &[node_string("x"), node_string(path)])
+                    .is_err()
+            );
+            assert_eq!(evaluator.get_num_errors(), i + 1);
+        }
+
+        assert_eq!(worker.lookup_data("x"), None);
+    }
+
+    #[test]
+    fn test_theme_config_strings() {
+        let evaluator = test_evaluator();
+        let mut worker = Worker::new(&evaluator);
+        LoadData
+            .handle(
+                &mut worker,
+                &[node_string("versions"), node_string("/data/versions.json")],
+            )
+            .unwrap();
+        worker.ctx.insert(
+            "release".to_owned(),
+            Arc::new(StoredValue::Node(node_string("v3.6"))),
+        );
+
+        let handler = ThemeConfig;
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[
+                    node_string("plain"),
+                    node_string("versions.server.latest"),
+                    node_string("defined"),
+                    node_children(vec![node_string("release")]),
+                    node_string("latest"),
+                    node_children(vec![node_string("versions.server.latest")]),
+                ]
+            ),
+            Ok("".to_owned())
+        );
+
+        // Only a bare reference to loaded data is looked up; other strings are kept as text
+        assert_eq!(
+            worker.theme_config.get("plain"),
+            Some(&json!("versions.server.latest"))
+        );
+        assert_eq!(worker.theme_config.get("defined"), Some(&json!("v3.6")));
+        assert_eq!(worker.theme_config.get("latest"), Some(&json!("3.6.1")));
+    }
+}
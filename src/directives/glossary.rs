@@ -25,8 +25,8 @@ impl DirectiveHandler for Glossary {
             result.push_str(&body);
             result.push_str("</dd>");
 
-            let refdef = RefDef::new(&term, worker.get_slug());
-            worker.insert_refdef(ref_id, refdef);
+            let refdef = RefDef::new(&term, worker.get_slug()).with_fragment(&ref_id);
+            worker.insert_refdef(node, ref_id, refdef);
         }
 
         result.push_str("</dl>");
@@ -1,7 +1,82 @@
+use std::cmp::Ordering;
 use parse::Node;
 use evaluator::Worker;
 use directives::{consume_string, DirectiveHandler};
 
+fn parse_number(value: &str) -> Result<f64, ()> {
+    match value.trim().parse::<f64>() {
+        Ok(n) if n.is_finite() => Ok(n),
+        _ => Err(()),
+    }
+}
+
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        (value as i64).to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Compare two dotted version strings such as "3.4.0" and "3.6.0-rc1". Missing components
+/// are treated as zero, and a pre-release sorts before the corresponding release.
+fn compare_versions(a: &str, b: &str) -> Result<Ordering, ()> {
+    fn split(version: &str) -> Result<(Vec<u64>, Option<&str>), ()> {
+        let version = version.trim();
+        let version = version.trim_start_matches('v');
+        let version = version.split('+').next().unwrap_or("");
+        let mut parts = version.splitn(2, '-');
+        let core = parts.next().unwrap_or("");
+        let prerelease = parts.next();
+
+        let components: Result<Vec<u64>, ()> = core.split('.')
+            .map(|c| c.parse::<u64>().or(Err(())))
+            .collect();
+        Ok((components?, prerelease))
+    }
+
+    fn compare_prerelease(a: &str, b: &str) -> Ordering {
+        let mut a_iter = a.split('.');
+        let mut b_iter = b.split('.');
+        loop {
+            let ordering = match (a_iter.next(), b_iter.next()) {
+                (None, None) => return Ordering::Equal,
+                (None, Some(_)) => return Ordering::Less,
+                (Some(_), None) => return Ordering::Greater,
+                (Some(a), Some(b)) => match (a.parse::<u64>(), b.parse::<u64>()) {
+                    (Ok(a), Ok(b)) => a.cmp(&b),
+                    (Ok(_), Err(_)) => Ordering::Less,
+                    (Err(_), Ok(_)) => Ordering::Greater,
+                    (Err(_), Err(_)) => a.cmp(b),
+                },
+            };
+
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+    }
+
+    let (a_core, a_prerelease) = split(a)?;
+    let (b_core, b_prerelease) = split(b)?;
+
+    for i in 0..a_core.len().max(b_core.len()) {
+        let a_component = a_core.get(i).cloned().unwrap_or(0);
+        let b_component = b_core.get(i).cloned().unwrap_or(0);
+        match a_component.cmp(&b_component) {
+            Ordering::Equal => (),
+            ordering => return Ok(ordering),
+        }
+    }
+
+    Ok(match (a_prerelease, b_prerelease) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => compare_prerelease(a, b),
+    })
+}
+
 pub struct If;
 
 impl DirectiveHandler for If {
@@ -81,6 +156,228 @@ impl DirectiveHandler for NotEquals {
     }
 }
 
+/// Returns the first empty argument, or the last argument if all are non-empty. Arguments
+/// after the first empty one are not evaluated.
+pub struct And;
+
+impl DirectiveHandler for And {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        if args.is_empty() {
+            return Err(());
+        }
+
+        let mut value = String::new();
+        for node in args {
+            value = worker.evaluate(node);
+            if value.is_empty() {
+                break;
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+/// Returns the first non-empty argument without evaluating the rest, or an empty string.
+pub struct Or;
+
+impl DirectiveHandler for Or {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        if args.is_empty() {
+            return Err(());
+        }
+
+        for node in args {
+            let value = worker.evaluate(node);
+            if !value.is_empty() {
+                return Ok(value);
+            }
+        }
+
+        Ok("".to_owned())
+    }
+}
+
+/// Takes condition/body pairs followed by an optional fallback, and evaluates the body of
+/// the first non-empty condition.
+pub struct Cond;
+
+impl DirectiveHandler for Cond {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        if args.len() < 2 {
+            return Err(());
+        }
+
+        for chunk in args.chunks(2) {
+            if chunk.len() == 1 {
+                return Ok(worker.evaluate(&chunk[0]));
+            }
+
+            if !worker.evaluate(&chunk[0]).is_empty() {
+                return Ok(worker.evaluate(&chunk[1]));
+            }
+        }
+
+        Ok("".to_owned())
+    }
+}
+
+/// Takes a value followed by case/body pairs and an optional fallback, and evaluates the body
+/// of the first case equal to the value.
+pub struct Switch;
+
+impl DirectiveHandler for Switch {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        if args.len() < 3 {
+            return Err(());
+        }
+
+        let mut iter = args.iter();
+        let value = consume_string(&mut iter, worker).ok_or(())?;
+
+        for chunk in args[1..].chunks(2) {
+            if chunk.len() == 1 {
+                return Ok(worker.evaluate(&chunk[0]));
+            }
+
+            if worker.evaluate(&chunk[0]) == value {
+                return Ok(worker.evaluate(&chunk[1]));
+            }
+        }
+
+        Ok("".to_owned())
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Comparison {
+    Equal,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn test(self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Equal => ordering == Ordering::Equal,
+            Comparison::Less => ordering == Ordering::Less,
+            Comparison::LessOrEqual => ordering != Ordering::Greater,
+            Comparison::Greater => ordering == Ordering::Greater,
+            Comparison::GreaterOrEqual => ordering != Ordering::Less,
+        }
+    }
+}
+
+/// Apply a comparison to each adjacent pair of arguments, so that (:< 1 2 3:) is true.
+fn compare_all<F>(
+    worker: &mut Worker,
+    args: &[Node],
+    comparison: Comparison,
+    compare: F,
+) -> Result<String, ()>
+where
+    F: Fn(&str, &str) -> Result<Ordering, ()>,
+{
+    if args.len() < 2 {
+        return Err(());
+    }
+
+    let values: Vec<String> = args.iter().map(|node| worker.evaluate(node)).collect();
+    for pair in values.windows(2) {
+        if !comparison.test(compare(&pair[0], &pair[1])?) {
+            return Ok("".to_owned());
+        }
+    }
+
+    Ok("true".to_owned())
+}
+
+pub struct NumericCompare {
+    comparison: Comparison,
+}
+
+impl NumericCompare {
+    pub fn new(comparison: Comparison) -> Self {
+        NumericCompare { comparison }
+    }
+}
+
+impl DirectiveHandler for NumericCompare {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        compare_all(worker, args, self.comparison, |a, b| {
+            let a = parse_number(a)?;
+            let b = parse_number(b)?;
+            a.partial_cmp(&b).ok_or(())
+        })
+    }
+}
+
+pub struct VersionCompare {
+    comparison: Comparison,
+}
+
+impl VersionCompare {
+    pub fn new(comparison: Comparison) -> Self {
+        VersionCompare { comparison }
+    }
+}
+
+impl DirectiveHandler for VersionCompare {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        compare_all(worker, args, self.comparison, compare_versions)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+}
+
+pub struct Arithmetic {
+    operator: Operator,
+}
+
+impl Arithmetic {
+    pub fn new(operator: Operator) -> Self {
+        Arithmetic { operator }
+    }
+}
+
+impl DirectiveHandler for Arithmetic {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        let mut iter = args.iter();
+        let initial = parse_number(&consume_string(&mut iter, worker).ok_or(())?)?;
+
+        if args.len() == 1 {
+            return match self.operator {
+                Operator::Subtract => Ok(format_number(-initial)),
+                _ => Ok(format_number(initial)),
+            };
+        }
+
+        let mut result = initial;
+        for node in iter {
+            let operand = parse_number(&worker.evaluate(node))?;
+            result = match self.operator {
+                Operator::Add => result + operand,
+                Operator::Subtract => result - operand,
+                Operator::Multiply => result * operand,
+                Operator::Divide | Operator::Modulo if operand == 0.0 => return Err(()),
+                Operator::Divide => result / operand,
+                Operator::Modulo => result % operand,
+            };
+        }
+
+        Ok(format_number(result))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -252,4 +549,288 @@ mod tests {
             Ok("true".to_owned())
         );
     }
+
+    #[test]
+    fn test_and() {
+        let mut evaluator = Evaluator::new();
+        evaluator.register_prelude("concat", Box::new(Concat));
+        let mut worker = Worker::new(&evaluator);
+        let handler = And;
+
+        assert!(handler.handle(&mut worker, &[]).is_err());
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string("foo"), node_string("bar")]),
+            Ok("bar".to_owned())
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[
+                    node_string("foo"),
+                    node_children(vec![node_string("concat"), node_string("")]),
+                    node_string("bar")
+                ]
+            ),
+            Ok("".to_owned())
+        );
+
+        // Arguments after the first empty value must not be evaluated
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[node_string(""), node_children(vec![node_string("undefined")])]
+            ),
+            Ok("".to_owned())
+        );
+        assert_eq!(evaluator.get_num_errors(), 0);
+    }
+
+    #[test]
+    fn test_or() {
+        let mut evaluator = Evaluator::new();
+        evaluator.register_prelude("concat", Box::new(Concat));
+        let mut worker = Worker::new(&evaluator);
+        let handler = Or;
+
+        assert!(handler.handle(&mut worker, &[]).is_err());
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string(""), node_string("")]),
+            Ok("".to_owned())
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[
+                    node_children(vec![node_string("concat"), node_string("")]),
+                    node_string("foo"),
+                    node_children(vec![node_string("undefined")])
+                ]
+            ),
+            Ok("foo".to_owned())
+        );
+        assert_eq!(evaluator.get_num_errors(), 0);
+    }
+
+    #[test]
+    fn test_cond() {
+        let evaluator = Evaluator::new();
+        let mut worker = Worker::new(&evaluator);
+        let handler = Cond;
+
+        assert!(handler.handle(&mut worker, &[]).is_err());
+        assert!(handler.handle(&mut worker, &[node_string("foo")]).is_err());
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[
+                    node_string(""),
+                    node_string("first"),
+                    node_string("true"),
+                    node_string("second"),
+                    node_string("fallback")
+                ]
+            ),
+            Ok("second".to_owned())
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[node_string(""), node_string("first"), node_string("fallback")]
+            ),
+            Ok("fallback".to_owned())
+        );
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string(""), node_string("first")]),
+            Ok("".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_switch() {
+        let evaluator = Evaluator::new();
+        let mut worker = Worker::new(&evaluator);
+        let handler = Switch;
+
+        assert!(handler.handle(&mut worker, &[]).is_err());
+        assert!(
+            handler
+                .handle(&mut worker, &[node_string("a"), node_string("a")])
+                .is_err()
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[
+                    node_string("b"),
+                    node_string("a"),
+                    node_string("first"),
+                    node_string("b"),
+                    node_string("second"),
+                    node_string("fallback")
+                ]
+            ),
+            Ok("second".to_owned())
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[
+                    node_string("c"),
+                    node_string("a"),
+                    node_string("first"),
+                    node_string("fallback")
+                ]
+            ),
+            Ok("fallback".to_owned())
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[node_string("c"), node_string("a"), node_string("first")]
+            ),
+            Ok("".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_numeric_compare() {
+        let mut evaluator = Evaluator::new();
+        evaluator.register_prelude("concat", Box::new(Concat));
+        let mut worker = Worker::new(&evaluator);
+
+        let handler = NumericCompare::new(Comparison::Less);
+        assert!(handler.handle(&mut worker, &[node_string("1")]).is_err());
+        assert!(
+            handler
+                .handle(&mut worker, &[node_string("1"), node_string("foo")])
+                .is_err()
+        );
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string("2"), node_string("10")]),
+            Ok("true".to_owned())
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[node_string("1"), node_string("3"), node_string("2")]
+            ),
+            Ok("".to_owned())
+        );
+
+        let handler = NumericCompare::new(Comparison::GreaterOrEqual);
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[
+                    node_children(vec![
+                        node_string("concat"),
+                        node_string("3."),
+                        node_string("5"),
+                    ]),
+                    node_string("3.5")
+                ]
+            ),
+            Ok("true".to_owned())
+        );
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string("-1"), node_string("0")]),
+            Ok("".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_version_compare() {
+        let evaluator = Evaluator::new();
+        let mut worker = Worker::new(&evaluator);
+
+        let handler = VersionCompare::new(Comparison::GreaterOrEqual);
+        assert!(handler.handle(&mut worker, &[node_string("3.2")]).is_err());
+        assert!(
+            handler
+                .handle(&mut worker, &[node_string("3.x"), node_string("3.2")])
+                .is_err()
+        );
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string("3.10.0"), node_string("3.2")]),
+            Ok("true".to_owned())
+        );
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string("3.2.0-rc1"), node_string("3.2")]),
+            Ok("".to_owned())
+        );
+
+        let handler = VersionCompare::new(Comparison::Equal);
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string("3.2"), node_string("v3.2.0")]),
+            Ok("true".to_owned())
+        );
+
+        let handler = VersionCompare::new(Comparison::Less);
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[node_string("3.2.0-alpha.2"), node_string("3.2.0-alpha.10")]
+            ),
+            Ok("true".to_owned())
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[node_string("3.2.0-alpha"), node_string("3.2.0-alpha.1")]
+            ),
+            Ok("true".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let evaluator = Evaluator::new();
+        let mut worker = Worker::new(&evaluator);
+
+        let handler = Arithmetic::new(Operator::Add);
+        assert!(handler.handle(&mut worker, &[]).is_err());
+        assert!(
+            handler
+                .handle(&mut worker, &[node_string("1"), node_string("")])
+                .is_err()
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[node_string("1"), node_string("2"), node_string("3")]
+            ),
+            Ok("6".to_owned())
+        );
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string("0.5"), node_string("0.25")]),
+            Ok("0.75".to_owned())
+        );
+
+        let handler = Arithmetic::new(Operator::Subtract);
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string("4")]),
+            Ok("-4".to_owned())
+        );
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string("10"), node_string("4")]),
+            Ok("6".to_owned())
+        );
+
+        let handler = Arithmetic::new(Operator::Divide);
+        assert!(
+            handler
+                .handle(&mut worker, &[node_string("1"), node_string("0")])
+                .is_err()
+        );
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string("7"), node_string("2")]),
+            Ok("3.5".to_owned())
+        );
+
+        let handler = Arithmetic::new(Operator::Modulo);
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string("7"), node_string("2")]),
+            Ok("1".to_owned())
+        );
+    }
 }
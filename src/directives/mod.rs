@@ -2,14 +2,20 @@ use std::borrow::Cow;
 use std::collections::hash_map::Entry;
 use std::sync::Arc;
 use std::{cmp, iter, mem, slice, str};
+use glob;
 use regex::{Captures, Regex};
 use serde_json;
 use parse::{Node, NodeValue};
-use page::Slug;
+use page::{PageHeading, Slug};
 use evaluator::{PlaceholderAction, RefDef, StoredValue, Worker};
+use tags::TagExpression;
+use toctree::{TocTreeElement, TocTreeOptions, TocTreeTarget};
+use theme;
 
+pub mod data;
 pub mod logic;
 pub mod glossary;
+pub mod string;
 
 fn consume_string(iter: &mut slice::Iter<Node>, worker: &mut Worker) -> Option<String> {
     match iter.next() {
@@ -50,6 +56,17 @@ pub fn concat_nodes(
         })
 }
 
+fn evaluate_tag_expression(worker: &mut Worker, node: &Node) -> Result<bool, ()> {
+    let text = worker.evaluate(node);
+    match TagExpression::parse(&text) {
+        Ok(expression) => Ok(worker.matches_tags(&expression)),
+        Err(msg) => {
+            worker.error(node, &format!("Invalid tag expression '{}': {}", text, msg));
+            Err(())
+        }
+    }
+}
+
 pub trait DirectiveHandler {
     fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()>;
 }
@@ -414,56 +431,270 @@ impl DirectiveHandler for ThemeConfig {
 
         for pair in args.chunks(2) {
             let key = worker.evaluate(&pair[0]);
-            let value = worker.evaluate(&pair[1]);
 
-            worker
-                .theme_config
-                .insert(key, serde_json::Value::String(value));
+            // A bare reference to loaded data is stored structurally rather than as a string
+            let data = match pair[1].value {
+                NodeValue::Children(ref children) if children.len() == 1 => {
+                    match children[0].value {
+                        NodeValue::Owned(ref name) => worker.lookup_data(name),
+                        NodeValue::Children(_) => None,
+                    }
+                }
+                _ => None,
+            };
+
+            let value = match data {
+                Some(value) => value,
+                None => serde_json::Value::String(worker.evaluate(&pair[1])),
+            };
+
+            worker.theme_config.insert(key, value);
         }
 
         Ok("".to_owned())
     }
 }
 
-pub struct TocTree;
+pub struct Only;
 
-impl DirectiveHandler for TocTree {
+impl DirectiveHandler for Only {
     fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        let mut iter = args.iter();
+        let expression = iter.next().ok_or(())?;
+
+        if evaluate_tag_expression(worker, expression)? {
+            Ok(concat_nodes(&mut iter, worker, ""))
+        } else {
+            Ok("".to_owned())
+        }
+    }
+}
+
+/// Leaves the current page out of the build unless the tag expression matches.
+pub struct PageOnly;
+
+impl DirectiveHandler for PageOnly {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        if args.len() != 1 {
+            return Err(());
+        }
+
+        if !evaluate_tag_expression(worker, &args[0])? {
+            worker.excluded = true;
+        }
+
+        Ok("".to_owned())
+    }
+}
+
+pub struct TocTree;
+
+impl TocTree {
+    fn parse_target(worker: &Worker, node: &Node, target: &str) -> Result<TocTreeTarget, ()> {
+        if target.contains("://") {
+            return Ok(TocTreeTarget::External(target.to_owned()));
+        }
+
+        let target = target.trim_matches('/');
+        if !target.contains(&['*', '?', '['][..]) {
+            return Ok(TocTreeTarget::Page(Slug::new(target.to_owned())));
+        }
+
+        match glob::Pattern::new(target) {
+            Ok(pattern) => Ok(TocTreeTarget::Glob(pattern)),
+            Err(err) => {
+                worker.error(node, &format!("Invalid toctree pattern '{}': {}", target, err));
+                Err(())
+            }
+        }
+    }
+
+    /// Parse the leading ":option:" arguments, returning the number of arguments consumed.
+    fn parse_options(
+        worker: &mut Worker,
+        args: &[Node],
+        options: &mut TocTreeOptions,
+        tree: &mut Option<String>,
+    ) -> Result<usize, ()> {
+        let mut i = 0;
+        while let Some(arg) = args.get(i) {
+            let option = match arg.value {
+                NodeValue::Owned(ref s) => s,
+                NodeValue::Children(_) => break,
+            };
+
+            if option.len() < 3 || !option.starts_with(':') || !option.ends_with(':') {
+                break;
+            }
+
+            let option = &option[1..option.len() - 1];
+
+            match option {
+                "hidden" => options.hidden = true,
+                "tree" => {
+                    i += 1;
+                    let name = args.get(i).map(|node| worker.evaluate(node)).ok_or(())?;
+                    *tree = Some(name.trim().to_owned());
+                }
+                "maxdepth" => {
+                    i += 1;
+                    let depth = args.get(i).map(|node| worker.evaluate(node)).ok_or(())?;
+                    match depth.trim().parse::<usize>() {
+                        Ok(depth) if depth > 0 => options.maxdepth = Some(depth),
+                        _ => {
+                            worker.error(arg, &format!("Invalid toctree maxdepth '{}'", depth));
+                            return Err(());
+                        }
+                    }
+                }
+                _ => {
+                    worker.error(arg, &format!("Unknown toctree option '{}'", option));
+                    return Err(());
+                }
+            }
+
+            i += 1;
+        }
+
+        Ok(i)
+    }
+
+    fn add_entries(
+        worker: &mut Worker,
+        args: &[Node],
+        options: TocTreeOptions,
+        tree: Option<&str>,
+    ) -> Result<(), ()> {
         for arg in args {
             match arg.value {
-                NodeValue::Owned(ref slug) => {
-                    worker.add_to_toctree(Slug::new(slug.to_owned()), None);
+                NodeValue::Owned(ref target) => {
+                    let target = Self::parse_target(worker, arg, target)?;
+                    worker.add_to_toctree(arg, tree, TocTreeElement::new(target, None, options))?;
                 }
                 NodeValue::Children(ref children) => {
+                    // (:only <tags> <entry> ...:) includes its entries only for matching builds
+                    let is_only = match children.first().map(|child| &child.value) {
+                        Some(NodeValue::Owned(name)) => name == "only",
+                        _ => false,
+                    };
+
+                    if is_only {
+                        if children.len() < 3 {
+                            let msg = "Expected a tag expression and entries after 'only'";
+                            worker.error(arg, msg);
+                            return Err(());
+                        }
+
+                        if evaluate_tag_expression(worker, &children[1])? {
+                            Self::add_entries(worker, &children[2..], options, tree)?;
+                        }
+
+                        continue;
+                    }
+
                     if children.len() != 2 {
+                        let msg = "Expected a toctree entry of the form (:<title> <page>:)";
+                        worker.error(arg, msg);
                         return Err(());
                     }
 
                     let title = worker.evaluate(&children[0]);
-                    let slug = worker.evaluate(&children[1]);
+                    let target = worker.evaluate(&children[1]);
+                    let target = Self::parse_target(worker, &children[1], &target)?;
 
-                    worker.add_to_toctree(Slug::new(slug), Some(title));
+                    let element = TocTreeElement::new(target, Some(title), options);
+                    worker.add_to_toctree(arg, tree, element)?;
                 }
             }
         }
 
+        Ok(())
+    }
+}
+
+impl DirectiveHandler for TocTree {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        let mut options = TocTreeOptions::default();
+        let mut tree = None;
+        let n_options = Self::parse_options(worker, args, &mut options, &mut tree)?;
+        Self::add_entries(worker, &args[n_options..], options, tree.as_deref())?;
         Ok(String::new())
     }
 }
 
+/// Marks the current page as intentionally absent from the toctree.
+pub struct Orphan;
+
+impl DirectiveHandler for Orphan {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        if !args.is_empty() {
+            return Err(());
+        }
+
+        worker.orphan = true;
+        Ok("".to_owned())
+    }
+}
+
+/// How automatic heading IDs are derived from titles.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HeadingIds {
+    /// Lowercased words joined by hyphens, preserving non-ASCII letters.
+    Unicode,
+
+    /// The original scheme, which spells out punctuation as decimal codepoints.
+    Legacy,
+
+    /// Unicode IDs, plus an empty anchor carrying the legacy ID so old links keep working.
+    Both,
+}
+
 pub struct Heading {
     level: i8,
     prefix : String,
+    ids: HeadingIds,
 }
 
 impl Heading {
+    #[cfg(test)]
     pub fn new(level: i8) -> Self {
+        Self::new_with_options(level, HeadingIds::Unicode)
+    }
+
+    pub fn new_with_options(level: i8, ids: HeadingIds) -> Self {
         let prefix = String::from("ref-");
 
-        Heading {level, prefix}
+        Heading {level, prefix, ids}
     }
 
     fn title_to_id(title: &str) -> String {
+        let title = theme::strip_tags(title);
+        let mut result = String::with_capacity(title.len());
+        let mut pending_separator = false;
+
+        // Collapse each run of whitespace and punctuation into a single hyphen
+        for c in title.chars() {
+            if c.is_alphanumeric() || c == '_' {
+                if pending_separator && !result.is_empty() {
+                    result.push('-');
+                }
+
+                pending_separator = false;
+                result.extend(c.to_lowercase());
+            } else {
+                pending_separator = true;
+            }
+        }
+
+        if result.is_empty() {
+            return Self::title_to_legacy_id(&title);
+        }
+
+        result
+    }
+
+    fn title_to_legacy_id(title: &str) -> String {
         let mut result = String::with_capacity(title.len());
 
         for c in title.chars() {
@@ -488,18 +719,61 @@ impl DirectiveHandler for Heading {
         let arg1 = consume_string(&mut iter, worker).ok_or(())?;
         let arg2 = consume_string(&mut iter, worker);
 
-        let (title, refdef) = match arg2 {
+        let mut prefix = worker.handle_heading(self.level)?;
+        let slug = worker.get_slug().to_owned();
+        let mut legacy_anchor = None;
+
+        let (title, id, refid) = match arg2 {
             Some(title) => {
-                let refdef = RefDef::new(&title, worker.get_slug());
-                worker.insert_refdef(format!("{}{}", self.prefix, arg1), refdef);
-                (title, arg1)
+                let id = worker.claim_id(&arg1);
+                let refid = format!("{}{}", self.prefix, arg1);
+                (title, id, refid)
             }
             None => {
-                let title_id = format!("{}{}", self.prefix, Self::title_to_id(&arg1));
-                (arg1, title_id)
+                // Links in the title aren't resolved until every page is built, so they are
+                // left out of the ID to keep it the same from one build to the next
+                let plain_title = worker.strip_placeholders(&arg1).into_owned();
+                let title_id = match self.ids {
+                    HeadingIds::Legacy => Self::title_to_legacy_id(&plain_title),
+                    HeadingIds::Unicode | HeadingIds::Both => Self::title_to_id(&plain_title),
+                };
+                let title_id = if title_id.is_empty() {
+                    "section".to_owned()
+                } else {
+                    title_id
+                };
+
+                // Automatic IDs are registered as "<prefix><slug>#<id>" so they can't collide
+                // across pages
+                let id = worker.claim_id(&format!("{}{}", self.prefix, title_id));
+                let refid = format!("{}{}#{}", self.prefix, slug, &id[self.prefix.len()..]);
+
+                if self.ids == HeadingIds::Both {
+                    let legacy_id = Self::title_to_legacy_id(&plain_title);
+                    let legacy_id = format!("{}{}", self.prefix, legacy_id);
+                    if legacy_id != id && legacy_id != self.prefix {
+                        legacy_anchor = Some(worker.claim_id(&legacy_id));
+                    }
+                }
+
+                (arg1, id, refid)
             }
         };
 
+        let mut refdef = RefDef::new(&title, &slug).with_fragment(&id);
+        let mut number = String::new();
+        if let Some(section) = worker.next_section_number(self.level) {
+            number = worker.get_placeholder(section.to_owned(), PlaceholderAction::SectionNumber);
+            refdef = refdef.with_section(&section);
+        }
+
+        worker.insert_refdef(&args[0], refid, refdef);
+        worker.headings.push(PageHeading {
+            level: self.level,
+            id: id.to_owned(),
+            title: title.to_owned(),
+        });
+
         if !worker.theme_config.contains_key("title") {
             worker.theme_config.insert(
                 "title".to_owned(),
@@ -507,13 +781,16 @@ impl DirectiveHandler for Heading {
             );
         }
 
-        let prefix = worker.handle_heading(self.level)?;
+        if let Some(legacy_id) = legacy_anchor {
+            prefix.push_str(&format!(r#"<span id="{}"></span>"#, escape_string(&legacy_id)));
+        }
 
         Ok(format!(
-            r#"{}<h{} id="{}">{}</h{}>"#,
+            r#"{}<h{} id="{}">{}{}</h{}>"#,
             prefix,
             self.level,
-            escape_string(&refdef),
+            escape_string(&id),
+            number,
             title,
             self.level
         ))
@@ -537,12 +814,36 @@ impl RefDefDirective {
 impl DirectiveHandler for RefDefDirective {
     fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
         let mut iter = args.iter();
-        let id = format!("{}{}", self.prefix, consume_string(&mut iter, worker).ok_or(())?);
-        let title = consume_string(&mut iter, worker).ok_or(())?;
+        let arg1 = consume_string(&mut iter, worker).ok_or(())?;
+        let arg2 = consume_string(&mut iter, worker).ok_or(())?;
+        let arg3 = consume_string(&mut iter, worker);
+
+        if iter.next().is_some() {
+            return Err(());
+        }
+
+        let (id, refdef) = match arg3 {
+            Some(title) => {
+                if arg1 != "override" {
+                    return Err(());
+                }
+
+                (arg2, RefDef::new_override(&title, worker.get_slug()))
+            }
+            None => (arg1, RefDef::new(&arg2, worker.get_slug())),
+        };
 
-        let refdef = RefDef::new(&title, worker.get_slug());
-        worker.insert_refdef(id, refdef);
+        let id = format!("{}{}", self.prefix, id);
 
+        // A reference defined partway through a page links to its own anchor
+        if worker.in_section() {
+            let anchor_id = worker.claim_id(&id);
+            let anchor = format!(r#"<span id="{}"></span>"#, escape_string(&anchor_id));
+            worker.insert_refdef(&args[0], id, refdef.with_fragment(&anchor_id));
+            return Ok(anchor);
+        }
+
+        worker.insert_refdef(&args[0], id, refdef);
         Ok(String::new())
     }
 }
@@ -563,7 +864,20 @@ impl RefDirective {
 impl DirectiveHandler for RefDirective {
     fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
         let mut iter = args.iter();
-        let refid = format!("{}{}", self.prefix, consume_string(&mut iter, worker).ok_or(())?);
+        let target = consume_string(&mut iter, worker).ok_or(())?;
+
+        // "#heading" is shorthand for a heading on the current page, and "project:id" refers
+        // to another project's inventory
+        let refid = if target.starts_with('#') {
+            format!("{}{}{}", self.prefix, worker.get_slug(), target)
+        } else {
+            match target.find(':') {
+                Some(i) if worker.is_external_project(&target[..i]) => {
+                    format!("{}:{}{}", &target[..i], self.prefix, &target[i + 1..])
+                }
+                _ => format!("{}{}", self.prefix, target),
+            }
+        };
 
         let title = match consume_string(&mut iter, worker) {
             Some(t) => t,
@@ -576,6 +890,29 @@ impl DirectiveHandler for RefDirective {
     }
 }
 
+/// Links to a page by its slug, using the page's title unless another is given.
+pub struct Doc;
+
+impl DirectiveHandler for Doc {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        let mut iter = args.iter();
+        let slug = consume_string(&mut iter, worker).ok_or(())?;
+        let slug = slug.trim_matches('/').to_owned();
+        if slug.is_empty() {
+            return Err(());
+        }
+
+        let title = match consume_string(&mut iter, worker) {
+            Some(t) => t,
+            None => worker.get_placeholder(slug.to_owned(), PlaceholderAction::PageTitle),
+        };
+
+        let placeholder = worker.get_placeholder(slug, PlaceholderAction::PagePath);
+
+        Ok(format!(r#"<a href="{}">{}</a>"#, placeholder, title))
+    }
+}
+
 pub struct Steps;
 
 impl DirectiveHandler for Steps {
@@ -685,6 +1022,29 @@ impl DirectiveHandler for Link {
     }
 }
 
+/// Declares former slugs of the current page, which will redirect to it.
+pub struct RedirectFrom;
+
+impl DirectiveHandler for RedirectFrom {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        if args.is_empty() {
+            return Err(());
+        }
+
+        for arg in args {
+            let from = worker.evaluate(arg);
+            let from = from.trim_matches('/');
+            if from.is_empty() {
+                return Err(());
+            }
+
+            worker.add_redirect(arg, Slug::new(from.to_owned()));
+        }
+
+        Ok("".to_owned())
+    }
+}
+
 pub struct List {
     tag: &'static str,
 }
@@ -716,7 +1076,9 @@ impl DirectiveHandler for List {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::{HashMap, HashSet};
     use evaluator::Evaluator;
+    use page::{Page, UrlConfig, UrlStyle};
 
     fn node_string(s: &str) -> Node {
         Node::new_string(s, 0, -1)
@@ -973,6 +1335,131 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_only() {
+        let mut evaluator = Evaluator::new();
+        evaluator.tags.insert("enterprise".to_owned());
+        let mut worker = Worker::new(&evaluator);
+        let handler = Only;
+
+        assert!(handler.handle(&mut worker, &[]).is_err());
+        assert!(
+            handler
+                .handle(&mut worker, &[node_string("enterprise and"), node_string("foo")])
+                .is_err()
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[node_string("enterprise"), node_string("foo"), node_string("bar")]
+            ),
+            Ok("foobar".to_owned())
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[node_string("not enterprise"), node_string("foo")]
+            ),
+            Ok("".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_toctree() {
+        let mut evaluator = Evaluator::new();
+        evaluator.add_toctree("footer", Slug::new("index".to_owned()));
+        {
+            let mut worker = Worker::new(&evaluator);
+            worker.set_slug(Slug::new("index".to_owned()));
+            let handler = TocTree;
+
+            assert!(handler.handle(&mut worker, &[node_string(":bogus:")]).is_err());
+            assert!(
+                handler
+                    .handle(&mut worker, &[node_string(":maxdepth:"), node_string("0")])
+                    .is_err()
+            );
+            assert!(handler.handle(&mut worker, &[node_string("guides/[")]).is_err());
+            assert!(handler.handle(&mut worker, &[node_children(vec![])]).is_err());
+            let only = node_children(vec![node_string("only"), node_string("enterprise")]);
+            assert!(handler.handle(&mut worker, &[only]).is_err());
+            assert!(
+                handler
+                    .handle(
+                        &mut worker,
+                        &[node_string(":tree:"), node_string("foter"), node_string("community")]
+                    )
+                    .is_err()
+            );
+            assert_eq!(evaluator.get_num_errors(), 6);
+
+            assert_eq!(
+                handler.handle(
+                    &mut worker,
+                    &[node_string(":tree:"), node_string("footer"), node_string("community")]
+                ),
+                Ok("".to_owned())
+            );
+
+            assert_eq!(
+                handler.handle(
+                    &mut worker,
+                    &[
+                        node_string(":maxdepth:"),
+                        node_string("1"),
+                        node_string("/guides/"),
+                        node_children(vec![
+                            node_string("Source"),
+                            node_string("https://example.com"),
+                        ]),
+                    ]
+                ),
+                Ok("".to_owned())
+            );
+
+            worker.set_slug(Slug::new("guides".to_owned()));
+            assert_eq!(
+                handler.handle(&mut worker, &[node_string(":hidden:"), node_string("guides/*")]),
+                Ok("".to_owned())
+            );
+        }
+
+        let mut titles = HashMap::new();
+        for slug in &["index", "guides", "guides/install", "community"] {
+            titles.insert(Slug::new((*slug).to_owned()), (*slug).to_owned());
+        }
+
+        let mut toctree = evaluator.toctree.write().unwrap();
+        toctree.finish(&titles);
+        evaluator
+            .named_toctrees
+            .write()
+            .unwrap()
+            .get_mut("footer")
+            .unwrap()
+            .finish(&titles);
+        let index = Slug::new("index".to_owned());
+        assert_eq!(
+            toctree.generate_html(&index, &index, true, None).unwrap().concat(),
+            concat!(
+                r#"<ul><li class="current"><a href="">index</a></li>"#,
+                r#"<li><a href="guides">guides</a></li>"#,
+                r#"<li class="external"><a href="https://example.com">Source</a></li></ul>"#
+            )
+        );
+        assert_eq!(
+            toctree.next(&Slug::new("guides".to_owned())),
+            Some(&Slug::new("guides/install".to_owned()))
+        );
+
+        let named_toctrees = evaluator.named_toctrees.read().unwrap();
+        let footer = &named_toctrees["footer"];
+        assert_eq!(
+            footer.generate_html(&index, &index, false, None).unwrap().concat(),
+            r#"<ul><li><a href="community">community</a></li></ul>"#
+        );
+    }
+
     #[test]
     fn test_heading() {
         let mut evaluator = Evaluator::new();
@@ -1015,18 +1502,200 @@ mod tests {
                 Ok(r#"</section></section><h1 id="ref-a-fourth-title">A Fourth Title</h1>"#.to_owned())
             );
 
-            assert_eq!(worker.close_sections(), "</section>".to_owned());
+            let handler = Heading::new(2);
+            assert_eq!(
+                handler.handle(&mut worker, &[node_string("A Third Title")]),
+                Ok(r#"<section><h2 id="ref-a-third-title-1">A Third Title</h2>"#.to_owned())
+            );
+
+            assert_eq!(worker.close_sections(), "</section></section>".to_owned());
+
+            let levels: Vec<_> = worker.headings.iter().map(|h| h.level).collect();
+            assert_eq!(levels, vec![1, 2, 3, 1, 2]);
+            assert_eq!(
+                worker.headings[4],
+                PageHeading {
+                    level: 2,
+                    id: "ref-a-third-title-1".to_owned(),
+                    title: "A Third Title".to_owned(),
+                }
+            );
         }
 
+        let refdefs = evaluator.refdefs.read().unwrap();
+        let refdef = refdefs.get("ref-a-title").unwrap();
+        assert_eq!(refdef.title, "A Title".to_owned());
+        assert_eq!(refdef.fragment, Some("a-title".to_owned()));
+        let urls = UrlConfig::default();
+        assert_eq!(
+            refdef.path_from(&Slug::new("reference".to_owned()), &urls),
+            "../#a-title".to_owned()
+        );
+        assert_eq!(
+            refdef.path_from(&Slug::new("index".to_owned()), &urls),
+            "#a-title".to_owned()
+        );
+
+        let urls = UrlConfig::new(UrlStyle::Flat, None);
+        assert_eq!(
+            refdef.path_from(&Slug::new("reference/api".to_owned()), &urls),
+            "../index.html#a-title".to_owned()
+        );
+
+        let urls = UrlConfig::new(UrlStyle::Flat, Some("https://example.com/docs/"));
+        assert_eq!(
+            refdef.path_from(&Slug::new("reference/api".to_owned()), &urls),
+            "https://example.com/docs/index.html#a-title".to_owned()
+        );
+
+        let refdef = refdefs.get("ref-index#a-second-title").unwrap();
+        assert_eq!(refdef.title, "A Second Title".to_owned());
+        assert_eq!(refdef.fragment, Some("ref-a-second-title".to_owned()));
+
+        let refdef = refdefs.get("ref-index#a-third-title-1").unwrap();
+        assert_eq!(refdef.fragment, Some("ref-a-third-title-1".to_owned()));
+    }
+
+    #[test]
+    fn test_title_to_id() {
+        assert_eq!(Heading::title_to_id("A Title"), "a-title".to_owned());
+        assert_eq!(Heading::title_to_id("C++ & Rust"), "c-rust".to_owned());
+        assert_eq!(Heading::title_to_id(" -- Déjà vu! -- "), "déjà-vu".to_owned());
+        assert_eq!(Heading::title_to_id("snake_case"), "snake_case".to_owned());
+        assert_eq!(Heading::title_to_id("<code>define</code>"), "define".to_owned());
+        assert_eq!(Heading::title_to_id("++"), "4343".to_owned());
+
+        assert_eq!(Heading::title_to_legacy_id("C++ & Rust"), "c4343-38-rust".to_owned());
+    }
+
+    #[test]
+    fn test_heading_ids() {
+        let evaluator = Evaluator::new();
+        let mut worker = Worker::new(&evaluator);
+        worker.set_slug(Slug::new("index".to_owned()));
+
+        let handler = Heading::new_with_options(1, HeadingIds::Legacy);
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string("C++")]),
+            Ok(r#"<section><h1 id="ref-c4343">C++</h1>"#.to_owned())
+        );
+
+        let handler = Heading::new_with_options(1, HeadingIds::Both);
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string("Rust & C")]),
+            Ok(r#"<span id="ref-rust-38-c"></span><h1 id="ref-rust-c">Rust & C</h1>"#.to_owned())
+        );
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string("Rust")]),
+            Ok(r#"<h1 id="ref-rust">Rust</h1>"#.to_owned())
+        );
+    }
+
+    #[test]
+    fn test_heading_with_link() {
+        let evaluator = Evaluator::new();
+        let mut worker = Worker::new(&evaluator);
+        worker.set_slug(Slug::new("guide".to_owned()));
+        let link = RefDirective::new("ref")
+            .handle(&mut worker, &[node_string("install")])
+            .unwrap();
+
+        let title = format!("Before {}", link);
+        let html = Heading::new(1).handle(&mut worker, &[node_string(&title)]).unwrap();
+        assert!(html.starts_with(r#"<section><h1 id="ref-before">"#));
+
+        let html = Heading::new(2).handle(&mut worker, &[node_string(&link)]).unwrap();
+        assert!(html.starts_with(r#"<section><h2 id="ref-section">"#));
+
+        let refdefs = evaluator.refdefs.read().unwrap();
+        assert!(refdefs.contains_key("ref-guide#before"));
+        assert!(refdefs.contains_key("ref-guide#section"));
+    }
+
+    #[test]
+    fn test_link_headings() {
+        let evaluator = Evaluator::new();
+        evaluator.titles.write().unwrap().insert(
+            Slug::new("tutorials/install".to_owned()),
+            "Installation".to_owned(),
+        );
+
+        let mut worker = Worker::new(&evaluator);
+        worker.set_slug(Slug::new("guide".to_owned()));
+        let ref_handler = RefDirective::new("ref");
+        let titles = [
+            "<code>Setup</code>".to_owned(),
+            ref_handler.handle(&mut worker, &[node_string("#setup")]).unwrap(),
+            Doc.handle(&mut worker, &[node_string("tutorials/install")]).unwrap(),
+            ref_handler.handle(&mut worker, &[node_string("missing")]).unwrap(),
+        ];
+        for (i, title) in titles.iter().enumerate() {
+            let level = if i == 0 { 1 } else { 2 };
+            Heading::new(level).handle(&mut worker, &[node_string(title)]).unwrap();
+        }
+
+        let mut page = Page::new_for_test("guide", "");
+        page.headings = worker.headings.clone();
+        let titles: Vec<_> = evaluator
+            .link_headings(&page)
+            .into_iter()
+            .map(|heading| heading.title)
+            .collect();
+        assert_eq!(titles, vec!["Setup", "Setup", "Installation", ""]);
+
+        // Unknown references are reported when the body is linked, not here
+        assert_eq!(evaluator.get_num_errors(), 0);
+    }
+
+    #[test]
+    fn test_section_numbers() {
+        let mut evaluator = Evaluator::new();
+        evaluator.enable_section_numbers();
+        evaluator
+            .page_numbers
+            .write()
+            .unwrap()
+            .insert(Slug::new("guide".to_owned()), "3".to_owned());
+
+        let mut body = String::new();
+        let mut worker = Worker::new(&evaluator);
+        worker.set_slug(Slug::new("guide".to_owned()));
+        for &(level, title) in &[(1, "Guide"), (2, "Setup"), (2, "Usage"), (3, "Options")] {
+            let heading = Heading::new(level).handle(&mut worker, &[node_string(title)]);
+            body.push_str(&heading.unwrap());
+        }
+
+        let handler = RefDirective::new("ref");
+        body.push_str(&handler.handle(&mut worker, &[node_string("#options")]).unwrap());
+
+        let page = Page::new_for_test("guide", &body);
+
+        let number = |n| format!(r#"<span class="section-number">{}</span> "#, n);
+        assert_eq!(
+            evaluator.substitute(&page).unwrap(),
+            format!(
+                concat!(
+                    r#"<section><h1 id="ref-guide">{}Guide</h1>"#,
+                    r#"<section><h2 id="ref-setup">{}Setup</h2>"#,
+                    r#"<h2 id="ref-usage">{}Usage</h2>"#,
+                    r#"<section><h3 id="ref-options">{}Options</h3>"#,
+                    r##"<a href="#ref-options">3.2.1 Options</a>"##
+                ),
+                number("3"),
+                number("3.1"),
+                number("3.2"),
+                number("3.2.1")
+            )
+        );
+
+        // Pages outside of the toctree aren't numbered
+        worker.set_slug(Slug::new("orphan".to_owned()));
+        let heading = Heading::new(1).handle(&mut worker, &[node_string("Orphan")]).unwrap();
+        let mut page = Page::new_for_test("orphan", &heading);
+        page.orphan = true;
         assert_eq!(
-            evaluator
-                .refdefs
-                .read()
-                .unwrap()
-                .get("ref-a-title")
-                .unwrap()
-                .title,
-            "A Title".to_owned()
+            evaluator.substitute(&page),
+            Ok(r#"<section><h1 id="ref-orphan">Orphan</h1>"#.to_owned())
         );
     }
 
@@ -1051,17 +1720,117 @@ mod tests {
                 ),
                 Ok(String::new())
             );
+
+            Heading::new(1)
+                .handle(&mut worker, &[node_string("Heading")])
+                .unwrap();
+            assert_eq!(
+                handler.handle(
+                    &mut worker,
+                    &[node_string("mid-page"), node_string("Mid Page")]
+                ),
+                Ok(r#"<span id="ref-mid-page"></span>"#.to_owned())
+            );
+        }
+
+        let refdefs = evaluator.refdefs.read().unwrap();
+        let refdef = refdefs.get("ref-a-title").unwrap();
+        assert_eq!(refdef.title, "A Title".to_owned());
+        assert_eq!(refdef.fragment, None);
+        assert_eq!(
+            refdefs.get("ref-mid-page").unwrap().fragment,
+            Some("ref-mid-page".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_duplicate_refdef() {
+        let evaluator = Evaluator::new();
+        {
+            let mut worker = Worker::new(&evaluator);
+            let handler = RefDefDirective::new("ref");
+
+            worker.set_slug(Slug::new("a".to_owned()));
+            assert!(
+                handler
+                    .handle(
+                        &mut worker,
+                        &[node_string("a-title"), node_string("A"), node_string("B")]
+                    )
+                    .is_err()
+            );
+            assert!(
+                handler
+                    .handle(&mut worker, &[node_string("foo"), node_string("A")])
+                    .is_ok()
+            );
+
+            worker.set_slug(Slug::new("b".to_owned()));
+            assert!(
+                handler
+                    .handle(&mut worker, &[node_string("foo"), node_string("B")])
+                    .is_ok()
+            );
+            assert_eq!(evaluator.get_num_errors(), 1);
+
+            // An override wins over a regular definition, whichever comes first
+            assert!(
+                handler
+                    .handle(
+                        &mut worker,
+                        &[node_string("override"), node_string("bar"), node_string("B")]
+                    )
+                    .is_ok()
+            );
+            worker.set_slug(Slug::new("a".to_owned()));
+            assert!(
+                handler
+                    .handle(&mut worker, &[node_string("bar"), node_string("A")])
+                    .is_ok()
+            );
+            assert_eq!(evaluator.get_num_errors(), 1);
+        }
+
+        let refdefs = evaluator.refdefs.read().unwrap();
+        assert_eq!(refdefs.get("ref-foo").unwrap().slug, Slug::new("a".to_owned()));
+        assert_eq!(refdefs.get("ref-bar").unwrap().slug, Slug::new("b".to_owned()));
+    }
+
+    #[test]
+    fn test_backlinks() {
+        let evaluator = Evaluator::new();
+        {
+            let mut worker = Worker::new(&evaluator);
+            let refdef = RefDefDirective::new("ref");
+            let reference = RefDirective::new("ref");
+
+            worker.set_slug(Slug::new("api".to_owned()));
+            refdef
+                .handle(&mut worker, &[node_string("api"), node_string("API")])
+                .unwrap();
+            reference.handle(&mut worker, &[node_string("api")]).unwrap();
+
+            for slug in &["tutorial", "faq", "faq"] {
+                worker.set_slug(Slug::new((*slug).to_owned()));
+                reference.handle(&mut worker, &[node_string("api")]).unwrap();
+                reference.handle(&mut worker, &[node_string("missing")]).unwrap();
+            }
+        }
+
+        {
+            let mut titles = evaluator.titles.write().unwrap();
+            titles.insert(Slug::new("tutorial".to_owned()), "Tutorial".to_owned());
+            titles.insert(Slug::new("faq".to_owned()), "FAQ".to_owned());
         }
 
+        let backlinks = evaluator.backlinks();
+        assert_eq!(backlinks.len(), 1);
         assert_eq!(
-            evaluator
-                .refdefs
-                .read()
-                .unwrap()
-                .get("ref-a-title")
-                .unwrap()
-                .title,
-            "A Title".to_owned()
+            backlinks.get(&Slug::new("api".to_owned())),
+            Some(&vec![
+                (Slug::new("faq".to_owned()), "FAQ".to_owned()),
+                (Slug::new("tutorial".to_owned()), "Tutorial".to_owned()),
+            ])
         );
     }
 
@@ -1135,6 +1904,123 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_doc() {
+        let evaluator = Evaluator::new();
+        evaluator.titles.write().unwrap().insert(
+            Slug::new("tutorials/install".to_owned()),
+            "Installation".to_owned(),
+        );
+
+        let mut worker = Worker::new(&evaluator);
+        worker.set_slug(Slug::new("reference/api".to_owned()));
+        let handler = Doc;
+
+        assert!(handler.handle(&mut worker, &[]).is_err());
+        assert!(handler.handle(&mut worker, &[node_string("/")]).is_err());
+
+        let first = handler
+            .handle(&mut worker, &[node_string("/tutorials/install")])
+            .unwrap();
+        let second = handler
+            .handle(&mut worker, &[node_string("index"), node_string("Home")])
+            .unwrap();
+
+        let page = Page::new_for_test("reference/api", &format!("{} {}", first, second));
+
+        assert_eq!(
+            evaluator.substitute(&page),
+            Ok(concat!(
+                r#"<a href="../../tutorials/install">Installation</a> "#,
+                r#"<a href="">Home</a>"#
+            ).to_owned())
+        );
+        assert_eq!(evaluator.get_num_errors(), 1);
+    }
+
+    #[test]
+    fn test_unknown_ref() {
+        let evaluator = Evaluator::new();
+        let mut worker = Worker::new(&evaluator);
+        worker.set_slug(Slug::new("index".to_owned()));
+        let handler = RefDirective::new("ref");
+
+        let body = handler.handle(&mut worker, &[node_string("missing")]).unwrap();
+        let page = Page::new_for_test("index", &body);
+
+        assert_eq!(evaluator.substitute(&page), Ok(r#"<a href=""></a>"#.to_owned()));
+        assert_eq!(evaluator.get_num_errors(), 1);
+    }
+
+    #[test]
+    fn test_redirect_from() {
+        let evaluator = Evaluator::new();
+        {
+            let mut worker = Worker::new(&evaluator);
+            worker.set_slug(Slug::new("tutorials/install".to_owned()));
+            let handler = RedirectFrom;
+
+            assert!(handler.handle(&mut worker, &[]).is_err());
+            assert_eq!(
+                handler.handle(
+                    &mut worker,
+                    &[node_string("/installation/"), node_string("setup")]
+                ),
+                Ok("".to_owned())
+            );
+        }
+
+        let redirects = evaluator.redirects.read().unwrap();
+        assert_eq!(redirects.len(), 2);
+        assert_eq!(redirects[0].from, Slug::new("installation".to_owned()));
+        assert_eq!(redirects[1].from, Slug::new("setup".to_owned()));
+        assert_eq!(redirects[1].to, Slug::new("tutorials/install".to_owned()));
+    }
+
+    #[test]
+    fn test_page_only() {
+        let mut evaluator = Evaluator::new();
+        evaluator.tags.insert("community".to_owned());
+        let mut worker = Worker::new(&evaluator);
+        worker.set_slug(Slug::new("guide/community".to_owned()));
+        let handler = PageOnly;
+
+        assert!(handler.handle(&mut worker, &[]).is_err());
+        assert_eq!(handler.handle(&mut worker, &[node_string("community")]), Ok("".to_owned()));
+        assert!(!worker.excluded);
+
+        worker.set_slug(Slug::new("guide/enterprise".to_owned()));
+        let heading = Heading::new(1).handle(&mut worker, &[node_string("Enterprise")]);
+        assert!(heading.is_ok());
+        worker.add_redirect(&node_string("x"), Slug::new("enterprise".to_owned()));
+        assert_eq!(handler.handle(&mut worker, &[node_string("enterprise")]), Ok("".to_owned()));
+        assert!(worker.excluded);
+
+        let excluded: HashSet<_> = vec![worker.get_slug().to_owned()].into_iter().collect();
+        evaluator.remove_pages(&excluded);
+        assert!(!evaluator.refdefs.read().unwrap().contains_key("ref-enterprise"));
+        assert!(evaluator.redirects.read().unwrap().is_empty());
+
+        worker.set_slug(Slug::new("index".to_owned()));
+        assert!(!worker.excluded);
+    }
+
+    #[test]
+    fn test_orphan() {
+        let evaluator = Evaluator::new();
+        let mut worker = Worker::new(&evaluator);
+        worker.set_slug(Slug::new("changelog".to_owned()));
+        let handler = Orphan;
+
+        assert!(handler.handle(&mut worker, &[node_string("x")]).is_err());
+        assert!(!worker.orphan);
+        assert_eq!(handler.handle(&mut worker, &[]), Ok("".to_owned()));
+        assert!(worker.orphan);
+
+        worker.set_slug(Slug::new("index".to_owned()));
+        assert!(!worker.orphan);
+    }
+
     #[test]
     fn test_link() {
         let mut evaluator = Evaluator::new();
@@ -0,0 +1,319 @@
+use regex::Regex;
+use parse::Node;
+use evaluator::Worker;
+use directives::{consume_string, DirectiveHandler};
+
+fn consume_single(worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+    if args.len() != 1 {
+        return Err(());
+    }
+
+    let mut iter = args.iter();
+    consume_string(&mut iter, worker).ok_or(())
+}
+
+pub struct Upper;
+
+impl DirectiveHandler for Upper {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        Ok(consume_single(worker, args)?.to_uppercase())
+    }
+}
+
+pub struct Lower;
+
+impl DirectiveHandler for Lower {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        Ok(consume_single(worker, args)?.to_lowercase())
+    }
+}
+
+pub struct Trim;
+
+impl DirectiveHandler for Trim {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        Ok(consume_single(worker, args)?.trim().to_owned())
+    }
+}
+
+pub struct Replace;
+
+impl DirectiveHandler for Replace {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        if args.len() != 3 {
+            return Err(());
+        }
+
+        let mut iter = args.iter();
+        let text = consume_string(&mut iter, worker).ok_or(())?;
+        let from = consume_string(&mut iter, worker).ok_or(())?;
+        let to = consume_string(&mut iter, worker).ok_or(())?;
+
+        if from.is_empty() {
+            return Err(());
+        }
+
+        Ok(text.replace(&from, &to))
+    }
+}
+
+/// Returns the first capture group of the pattern if it has one, otherwise the whole match.
+/// Yields an empty string if the pattern doesn't match, so it can be used as a condition.
+pub struct Match;
+
+impl DirectiveHandler for Match {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        if args.len() != 2 {
+            return Err(());
+        }
+
+        let mut iter = args.iter();
+        let pattern = consume_string(&mut iter, worker).ok_or(())?;
+        let text = consume_string(&mut iter, worker).ok_or(())?;
+        let pattern = match Regex::new(&pattern) {
+            Ok(pattern) => pattern,
+            Err(err) => {
+                worker.error(&args[0], &format!("Invalid pattern '{}': {}", pattern, err));
+                return Err(());
+            }
+        };
+
+        let captures = match pattern.captures(&text) {
+            Some(c) => c,
+            None => return Ok("".to_owned()),
+        };
+
+        let matched = captures.get(1).or_else(|| captures.get(0));
+        Ok(matched.map(|m| m.as_str().to_owned()).unwrap_or_default())
+    }
+}
+
+/// Returns a single component of the split text. Negative indices count from the end.
+pub struct Split;
+
+impl DirectiveHandler for Split {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        if args.len() != 3 {
+            return Err(());
+        }
+
+        let mut iter = args.iter();
+        let text = consume_string(&mut iter, worker).ok_or(())?;
+        let separator = consume_string(&mut iter, worker).ok_or(())?;
+        let index = consume_string(&mut iter, worker).ok_or(())?;
+        let index = index.trim().parse::<isize>().or(Err(()))?;
+
+        if separator.is_empty() {
+            return Err(());
+        }
+
+        let components: Vec<&str> = text.split(separator.as_str()).collect();
+        let index = if index < 0 {
+            components.len() as isize + index
+        } else {
+            index
+        };
+
+        if index < 0 {
+            return Ok("".to_owned());
+        }
+
+        Ok(components
+            .get(index as usize)
+            .map(|s| (*s).to_owned())
+            .unwrap_or_default())
+    }
+}
+
+/// Joins its arguments with a separator, skipping any that evaluate to an empty string.
+pub struct Join;
+
+impl DirectiveHandler for Join {
+    fn handle(&self, worker: &mut Worker, args: &[Node]) -> Result<String, ()> {
+        let mut iter = args.iter();
+        let separator = consume_string(&mut iter, worker).ok_or(())?;
+        let components: Vec<String> = iter.map(|node| worker.evaluate(node))
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        Ok(components.join(&separator))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use directives::*;
+    use evaluator::Evaluator;
+
+    fn node_string(s: &str) -> Node {
+        Node::new_string(s, 0, -1)
+    }
+
+    fn node_children(nodes: Vec<Node>) -> Node {
+        Node::new_children(nodes, 0, -1)
+    }
+
+    #[test]
+    fn test_case() {
+        let mut evaluator = Evaluator::new();
+        evaluator.register_prelude("concat", Box::new(Concat));
+        let mut worker = Worker::new(&evaluator);
+
+        assert!(Upper.handle(&mut worker, &[]).is_err());
+        assert!(
+            Upper
+                .handle(&mut worker, &[node_string("a"), node_string("b")])
+                .is_err()
+        );
+        assert_eq!(
+            Upper.handle(&mut worker, &[node_string("Crème brûlée")]),
+            Ok("CRÈME BRÛLÉE".to_owned())
+        );
+        assert_eq!(
+            Lower.handle(
+                &mut worker,
+                &[
+                    node_children(vec![
+                        node_string("concat"),
+                        node_string("FOO"),
+                        node_string("Bar"),
+                    ])
+                ]
+            ),
+            Ok("foobar".to_owned())
+        );
+        assert_eq!(
+            Trim.handle(&mut worker, &[node_string("  foo bar\n")]),
+            Ok("foo bar".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_replace() {
+        let evaluator = Evaluator::new();
+        let mut worker = Worker::new(&evaluator);
+        let handler = Replace;
+
+        assert!(handler.handle(&mut worker, &[]).is_err());
+        assert!(
+            handler
+                .handle(
+                    &mut worker,
+                    &[node_string("foo"), node_string(""), node_string("x")]
+                )
+                .is_err()
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[node_string("Getting Started"), node_string(" "), node_string("-")]
+            ),
+            Ok("Getting-Started".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_match() {
+        let evaluator = Evaluator::new();
+        let mut worker = Worker::new(&evaluator);
+        let handler = Match;
+
+        assert!(handler.handle(&mut worker, &[node_string("foo")]).is_err());
+        assert!(
+            handler
+                .handle(&mut worker, &[node_string("("), node_string("foo")])
+                .is_err()
+        );
+        assert_eq!(evaluator.get_num_errors(), 1);
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[node_string("^3\\.[0-9]+"), node_string("3.4.0")]
+            ),
+            Ok("3.4".to_owned())
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[node_string("^([0-9]+)\\."), node_string("3.4.0")]
+            ),
+            Ok("3".to_owned())
+        );
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string("^4"), node_string("3.4.0")]),
+            Ok("".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_split() {
+        let evaluator = Evaluator::new();
+        let mut worker = Worker::new(&evaluator);
+        let handler = Split;
+
+        assert!(handler.handle(&mut worker, &[]).is_err());
+        assert!(
+            handler
+                .handle(
+                    &mut worker,
+                    &[node_string("a.b"), node_string("."), node_string("x")]
+                )
+                .is_err()
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[node_string("a.b.c"), node_string("."), node_string("1")]
+            ),
+            Ok("b".to_owned())
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[node_string("a.b.c"), node_string("."), node_string("-1")]
+            ),
+            Ok("c".to_owned())
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[node_string("a.b.c"), node_string("."), node_string("3")]
+            ),
+            Ok("".to_owned())
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[node_string("a.b.c"), node_string("."), node_string("-4")]
+            ),
+            Ok("".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_join() {
+        let mut evaluator = Evaluator::new();
+        evaluator.register_prelude("concat", Box::new(Concat));
+        let mut worker = Worker::new(&evaluator);
+        let handler = Join;
+
+        assert!(handler.handle(&mut worker, &[]).is_err());
+        assert_eq!(
+            handler.handle(&mut worker, &[node_string(", ")]),
+            Ok("".to_owned())
+        );
+        assert_eq!(
+            handler.handle(
+                &mut worker,
+                &[
+                    node_string(", "),
+                    node_string("foo"),
+                    node_children(vec![node_string("concat"), node_string("")]),
+                    node_string("bar"),
+                ]
+            ),
+            Ok("foo, bar".to_owned())
+        );
+    }
+}
@@ -1,5 +1,8 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::cmp;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::marker::Sync;
 use std::path::{Path, PathBuf};
 use std::sync::{atomic, Arc, RwLock};
@@ -10,19 +13,58 @@ use rand::Rng;
 use regex::{Captures, Regex};
 use directives;
 use highlighter::{self, SyntaxHighlighter};
-use page::{Page, Slug};
+use inventory::{ExternalProject, InventoryEntry};
+use page::{Page, PageHeading, Slug, UrlConfig};
 use parse::{Node, NodeValue, Parser};
-use toctree::TocTree;
+use suggest;
+use tags::TagExpression;
+use theme;
+use toctree::{TocTree, TocTreeElement, MAIN_TOCTREE};
 
 pub enum PlaceholderAction {
     Path,
     Title,
+
+    /// Resolve a page's slug rather than a reference ID.
+    PagePath,
+    PageTitle,
+
+    /// Insert the section number of a heading, given its number within the page.
+    SectionNumber,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceLocation {
+    pub path: PathBuf,
+    pub lineno: i32,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.lineno >= 0 {
+            write!(f, "{}:{}", self.path.to_string_lossy(), self.lineno)
+        } else {
+            write!(f, "{}:?", self.path.to_string_lossy())
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct RefDef {
     pub title: String,
     pub slug: Slug,
+
+    /// The id of the anchor within the page, if the reference doesn't point at the whole page.
+    pub fragment: Option<String>,
+
+    /// Where this reference was defined. Filled in by Worker::insert_refdef.
+    pub location: Option<SourceLocation>,
+
+    /// An override silently replaces any other definition with the same ID.
+    pub is_override: bool,
+
+    /// The number of the heading within its page, if section numbering is enabled.
+    pub section: Option<String>,
 }
 
 impl RefDef {
@@ -30,24 +72,86 @@ impl RefDef {
         RefDef {
             title: title.to_owned(),
             slug: slug.to_owned(),
+            fragment: None,
+            location: None,
+            is_override: false,
+            section: None,
+        }
+    }
+
+    pub fn with_fragment(self, fragment: &str) -> Self {
+        RefDef {
+            fragment: Some(fragment.to_owned()),
+            ..self
+        }
+    }
+
+    pub fn with_section(self, section: &str) -> Self {
+        RefDef {
+            section: Some(section.to_owned()),
+            ..self
+        }
+    }
+
+    /// Return the URL of this reference as seen from the given page.
+    pub fn path_from(&self, slug: &Slug, urls: &UrlConfig) -> String {
+        match self.fragment {
+            Some(ref fragment) if self.slug == *slug => format!("#{}", fragment),
+            Some(ref fragment) => {
+                format!("{}#{}", urls.page_url_from(slug, &self.slug), fragment)
+            }
+            None => urls.page_url_from(slug, &self.slug),
         }
     }
+
+    pub fn new_override(title: &str, slug: &Slug) -> Self {
+        RefDef {
+            is_override: true,
+            ..Self::new(title, slug)
+        }
+    }
+}
+
+/// A former location of a page, which should send readers to the page's current URL.
+pub struct Redirect {
+    pub from: Slug,
+    pub to: Slug,
+
+    /// Where this redirect was declared, or None if it came from config.toml.
+    pub location: Option<SourceLocation>,
 }
 
 pub enum StoredValue {
     Directive(Box<directives::DirectiveHandler + Sync + Send>),
     Node(Node),
+    Data(serde_json::Value),
 }
 
 pub struct Evaluator {
     pub root_path: PathBuf,
+    pub urls: UrlConfig,
     prelude_ctx: HashMap<String, Arc<StoredValue>>,
     pub refdefs: RwLock<HashMap<String, RefDef>>,
     pub toctree: RwLock<TocTree>,
 
+    /// Additional navigation trees, such as a footer menu, each with its own root.
+    pub named_toctrees: RwLock<HashMap<String, TocTree>>,
+    pub tags: HashSet<String>,
+    pub external_projects: HashMap<String, ExternalProject>,
+    pub titles: RwLock<HashMap<Slug, String>>,
+    pub redirects: RwLock<Vec<Redirect>>,
+
+    /// True if pages and headings are numbered by their position in the toctree.
+    pub section_numbers: bool,
+
+    /// The section number of each page, filled in once the toctree is finished.
+    pub page_numbers: RwLock<HashMap<Slug, String>>,
+
     placeholder_pattern: Regex,
     placeholder_prefix: String,
-    pub pending_links: RwLock<Vec<(PlaceholderAction, String)>>,
+
+    /// Every reference placeholder emitted so far, along with the page that contains it.
+    pub pending_links: RwLock<Vec<(PlaceholderAction, String, Slug)>>,
 
     errors: atomic::AtomicUsize,
 }
@@ -55,10 +159,10 @@ pub struct Evaluator {
 impl Evaluator {
     #[allow(dead_code)]
     pub fn new() -> Self {
-        Self::new_with_options(PathBuf::new())
+        Self::new_with_options(PathBuf::new(), UrlConfig::default())
     }
 
-    pub fn new_with_options(root_path: PathBuf) -> Self {
+    pub fn new_with_options(root_path: PathBuf, urls: UrlConfig) -> Self {
         let hex_chars = b"0123456789abcdef";
         let mut rnd_buf = [0u8; 16];
         rand::thread_rng().fill_bytes(&mut rnd_buf);
@@ -72,11 +176,21 @@ impl Evaluator {
         let placeholder_pattern =
             Regex::new(&pattern_text).expect("Failed to compile linker pattern");
 
+        let toctree = TocTree::new(Slug::new("index".to_owned()), urls.clone());
+
         Evaluator {
             root_path,
+            urls,
             prelude_ctx: HashMap::new(),
             refdefs: RwLock::new(HashMap::new()),
-            toctree: RwLock::new(TocTree::new(Slug::new("index".to_owned()), true)),
+            toctree: RwLock::new(toctree),
+            named_toctrees: RwLock::new(HashMap::new()),
+            tags: HashSet::new(),
+            external_projects: HashMap::new(),
+            titles: RwLock::new(HashMap::new()),
+            redirects: RwLock::new(vec![]),
+            section_numbers: false,
+            page_numbers: RwLock::new(HashMap::new()),
 
             placeholder_pattern,
             placeholder_prefix,
@@ -89,6 +203,46 @@ impl Evaluator {
         self.errors.load(atomic::Ordering::Relaxed)
     }
 
+    /// Register a named toctree with the given root page. The main tree's root can be
+    /// changed the same way.
+    pub fn add_toctree(&mut self, name: &str, root: Slug) {
+        if name == MAIN_TOCTREE {
+            self.toctree.get_mut().unwrap().set_root(root);
+            return;
+        }
+
+        let toctree = TocTree::new(root, self.urls.clone());
+        self.named_toctrees
+            .get_mut()
+            .unwrap()
+            .insert(name.to_owned(), toctree);
+    }
+
+    /// Number pages and their headings by their position in the toctree.
+    pub fn enable_section_numbers(&mut self) {
+        self.section_numbers = true;
+        self.toctree.get_mut().unwrap().set_numbered(true);
+    }
+
+    /// Forget the references defined on pages that were left out of the build, along with
+    /// any redirects to them.
+    pub fn remove_pages(&self, slugs: &HashSet<Slug>) {
+        self.refdefs
+            .write()
+            .unwrap()
+            .retain(|_, refdef| !slugs.contains(&refdef.slug));
+        self.redirects
+            .write()
+            .unwrap()
+            .retain(|redirect| !slugs.contains(&redirect.to));
+    }
+
+    /// Log an error that isn't tied to a particular node, and fail the build.
+    pub fn report_error(&self, message: &str) {
+        error!("{}", message);
+        self.errors.fetch_add(1, atomic::Ordering::Relaxed);
+    }
+
     pub fn register_prelude<S: Into<String>>(
         &mut self,
         name: S,
@@ -99,32 +253,187 @@ impl Evaluator {
     }
 
     pub fn substitute(&self, page: &Page) -> Result<String, ()> {
+        Ok(self.substitute_text(&page.body, page))
+    }
+
+    /// Replace the placeholders in text from the given page with the links they stand for.
+    fn substitute_text(&self, text: &str, page: &Page) -> String {
         let result = self.placeholder_pattern
-            .replace_all(&page.body, |captures: &Captures| {
+            .replace_all(text, |captures: &Captures| {
                 let ref_number = str::parse::<u64>(&captures[1]).expect("Failed to parse refid");
-                let r1 = self.pending_links.read().unwrap();
-                let r2 = self.refdefs.read().unwrap();
-                let &(ref action, ref refid) =
-                    r1.get(ref_number as usize).expect("Missing ref number");
-                let refdef = match r2.get(refid) {
-                    Some(r) => r,
-                    None => {
-                        error!(
-                            "Unknown reference '{}' used in page {}",
-                            refid,
-                            page.source_path.to_string_lossy()
-                        );
-                        return "".to_owned();
-                    }
-                };
+                let pending_links = self.pending_links.read().unwrap();
+                let &(ref action, ref target, _) = pending_links
+                    .get(ref_number as usize)
+                    .expect("Missing ref number");
 
                 match *action {
-                    PlaceholderAction::Path => page.slug.path_to(refdef.slug.as_ref(), true),
-                    PlaceholderAction::Title => refdef.title.to_owned(),
+                    PlaceholderAction::Path | PlaceholderAction::Title => {
+                        self.resolve_ref(action, target, page)
+                    }
+                    PlaceholderAction::PagePath | PlaceholderAction::PageTitle => {
+                        self.resolve_page(action, target, page)
+                    }
+                    PlaceholderAction::SectionNumber => {
+                        match self.section_number(&page.slug, target) {
+                            Some(number) => {
+                                format!(r#"<span class="section-number">{}</span> "#, number)
+                            }
+                            None => "".to_owned(),
+                        }
+                    }
                 }
             });
 
-        Ok(result.into_owned())
+        result.into_owned()
+    }
+
+    /// Resolve a title from the given page to plain text. Links are removed before
+    /// substituting, so that an unknown reference isn't reported a second time.
+    pub fn link_title(&self, title: &str, page: &Page) -> String {
+        let title = self.substitute_text(&theme::strip_tags(title), page);
+        theme::strip_tags(&title).into_owned()
+    }
+
+    /// Return the page's headings with their titles resolved to plain text, for the outline
+    /// and the search index.
+    pub fn link_headings(&self, page: &Page) -> Vec<PageHeading> {
+        page.headings
+            .iter()
+            .map(|heading| PageHeading {
+                title: self.link_title(&heading.title, page),
+                ..heading.clone()
+            })
+            .collect()
+    }
+
+    /// Return the full section number of a heading, given its number within its page. Pages
+    /// outside of the toctree aren't numbered.
+    fn section_number(&self, slug: &Slug, section: &str) -> Option<String> {
+        let page_numbers = self.page_numbers.read().unwrap();
+        let number = page_numbers.get(slug)?;
+        if section.is_empty() {
+            Some(number.to_owned())
+        } else {
+            Some(format!("{}.{}", number, section))
+        }
+    }
+
+    fn resolve_ref(&self, action: &PlaceholderAction, refid: &str, page: &Page) -> String {
+        let refdefs = self.refdefs.read().unwrap();
+        if let Some(refdef) = refdefs.get(refid) {
+            return match *action {
+                PlaceholderAction::Path => refdef.path_from(&page.slug, &self.urls),
+                _ => {
+                    let number = refdef
+                        .section
+                        .as_ref()
+                        .and_then(|section| self.section_number(&refdef.slug, section));
+                    match number {
+                        Some(number) => format!("{} {}", number, refdef.title),
+                        None => refdef.title.to_owned(),
+                    }
+                }
+            };
+        }
+
+        if let Some((project, entry)) = self.lookup_external(refid) {
+            return match *action {
+                PlaceholderAction::Path => project.url(entry),
+                _ => entry.title.to_owned(),
+            };
+        }
+
+        if let PlaceholderAction::Path = *action {
+            let suggestion = suggest::suggest(refid, refdefs.keys().map(|k| k.as_str()));
+            self.report_unknown("reference", refid, page, suggestion);
+        }
+
+        "".to_owned()
+    }
+
+    fn resolve_page(&self, action: &PlaceholderAction, slug: &str, page: &Page) -> String {
+        let slug = Slug::new(slug.to_owned());
+        if let Some(title) = self.titles.read().unwrap().get(&slug) {
+            return match *action {
+                PlaceholderAction::PagePath => self.urls.page_url_from(&page.slug, &slug),
+                _ => match self.section_number(&slug, "") {
+                    Some(number) => format!("{} {}", number, title),
+                    None => title.to_owned(),
+                },
+            };
+        }
+
+        if let PlaceholderAction::PagePath = *action {
+            self.report_unknown("page", slug.as_ref(), page, None);
+        }
+
+        "".to_owned()
+    }
+
+    /// Report a link to an unknown reference or page, and fail the build. Callers only
+    /// report a link's path, since every link has one but not necessarily a title.
+    fn report_unknown(&self, kind: &str, target: &str, page: &Page, suggestion: Option<&str>) {
+        let message = format!(
+            "Unknown {} '{}' used in page {}",
+            kind,
+            target,
+            page.source_path.to_string_lossy()
+        );
+
+        match suggestion {
+            Some(suggestion) => {
+                self.report_error(&format!("{}; did you mean '{}'?", message, suggestion))
+            }
+            None => self.report_error(&message),
+        }
+    }
+
+    /// Return the pages that link to each page, sorted by title. Links from a page to
+    /// itself and to other projects are ignored.
+    pub fn backlinks(&self) -> HashMap<Slug, Vec<(Slug, String)>> {
+        let pending_links = self.pending_links.read().unwrap();
+        let refdefs = self.refdefs.read().unwrap();
+        let titles = self.titles.read().unwrap();
+
+        let mut sources: HashMap<Slug, HashSet<Slug>> = HashMap::new();
+        for (action, target, source) in pending_links.iter() {
+            let dest = match *action {
+                PlaceholderAction::Path => match refdefs.get(target) {
+                    Some(refdef) => refdef.slug.to_owned(),
+                    None => continue,
+                },
+                PlaceholderAction::PagePath => Slug::new(target.to_owned()),
+                _ => continue,
+            };
+
+            if dest != *source {
+                sources.entry(dest).or_default().insert(source.to_owned());
+            }
+        }
+
+        sources
+            .into_iter()
+            .map(|(slug, sources)| {
+                let mut pages: Vec<_> = sources
+                    .into_iter()
+                    .filter_map(|source| {
+                        let title = titles.get(&source)?.to_owned();
+                        Some((source, title))
+                    })
+                    .collect();
+
+                pages.sort_by(|a, b| (&a.1, a.0.as_ref()).cmp(&(&b.1, b.0.as_ref())));
+                (slug, pages)
+            })
+            .collect()
+    }
+
+    /// Resolve a reference of the form "project:id" against another project's inventory.
+    fn lookup_external(&self, refid: &str) -> Option<(&ExternalProject, &InventoryEntry)> {
+        let mut parts = refid.splitn(2, ':');
+        let project = self.external_projects.get(parts.next()?)?;
+        let entry = project.get(parts.next()?)?;
+        Some((project, entry))
     }
 }
 
@@ -133,11 +442,22 @@ pub struct Worker<'a> {
 
     current_slug: Option<Slug>,
     current_level: i8,
+    used_ids: HashSet<String>,
+
+    /// The number of headings seen at each level below the page title, for section numbering.
+    section_counters: Vec<usize>,
     pub parser: Parser,
 
     evaluator: &'a Evaluator,
     pub ctx: HashMap<String, Arc<StoredValue>>,
     pub theme_config: serde_json::map::Map<String, serde_json::Value>,
+    pub headings: Vec<PageHeading>,
+
+    /// Set by the orphan directive on pages that are deliberately left out of the toctree.
+    pub orphan: bool,
+
+    /// Set by the page-only directive on pages that are left out of the build.
+    pub excluded: bool,
 }
 
 impl<'a> Worker<'a> {
@@ -151,10 +471,15 @@ impl<'a> Worker<'a> {
             highlighter: SyntaxHighlighter::new(syntax_theme),
             current_slug: None,
             current_level: 0,
+            used_ids: HashSet::new(),
+            section_counters: vec![],
             parser: Parser::new(),
             evaluator: evaluator,
             ctx: HashMap::new(),
             theme_config: serde_json::map::Map::new(),
+            headings: vec![],
+            orphan: false,
+            excluded: false,
         }
     }
 
@@ -187,7 +512,22 @@ impl<'a> Worker<'a> {
         {
             Some(val) => Arc::clone(val),
             None => {
-                self.error(node, &format!("Unknown name: '{}'", key));
+                if let Some(value) = self.lookup_data(key) {
+                    return Ok(directives::data::stringify(&value));
+                }
+
+                let candidates = self.ctx
+                    .keys()
+                    .chain(self.evaluator.prelude_ctx.keys())
+                    .map(|k| k.as_str());
+                let message = match suggest::suggest(key, candidates) {
+                    Some(suggestion) => {
+                        format!("Unknown name: '{}'; did you mean '{}'?", key, suggestion)
+                    }
+                    None => format!("Unknown name: '{}'", key),
+                };
+
+                self.error(node, &message);
                 return Err(());
             }
         };
@@ -195,14 +535,46 @@ impl<'a> Worker<'a> {
         match *stored {
             StoredValue::Node(ref stored_node) => Ok(self.evaluate(stored_node)),
             StoredValue::Directive(ref handler) => handler.handle(self, args),
+            StoredValue::Data(ref value) => Ok(directives::data::stringify(value)),
+        }
+    }
+
+    /// Resolve a dotted path such as "versions.server.latest" against a loaded data file.
+    pub fn lookup_data(&self, key: &str) -> Option<serde_json::Value> {
+        let mut components = key.split('.');
+        let root = components.next()?;
+        let stored = self.ctx
+            .get(root)
+            .or_else(|| self.evaluator.prelude_ctx.get(root))?;
+
+        let mut value = match **stored {
+            StoredValue::Data(ref value) => value,
+            _ => return None,
+        };
+
+        for component in components {
+            value = match *value {
+                serde_json::Value::Object(ref map) => map.get(component)?,
+                serde_json::Value::Array(ref array) => {
+                    array.get(component.parse::<usize>().ok()?)?
+                }
+                _ => return None,
+            };
         }
+
+        Some(value.clone())
     }
 
     pub fn set_slug(&mut self, slug: Slug) {
         self.current_slug = Some(slug);
         self.current_level = 0;
+        self.used_ids.clear();
+        self.section_counters.clear();
         self.ctx.clear();
         self.theme_config.clear();
+        self.headings.clear();
+        self.orphan = false;
+        self.excluded = false;
     }
 
     pub fn get_slug(&self) -> &Slug {
@@ -216,7 +588,7 @@ impl<'a> Worker<'a> {
         let slug = self.current_slug
             .as_ref()
             .expect("current_slug not yet initialized");
-        Ok(slug.path_to(output_slug.as_ref(), true))
+        Ok(self.evaluator.urls.url_from(slug, output_slug.as_ref()))
     }
 
     pub fn register<S: Into<String>>(
@@ -229,26 +601,111 @@ impl<'a> Worker<'a> {
     }
 
     pub fn get_placeholder(&mut self, refid: String, action: PlaceholderAction) -> String {
+        let source = self.get_slug().to_owned();
         let mut txn = self.evaluator.pending_links.write().unwrap();
-        txn.push((action, refid));
+        txn.push((action, refid, source));
         format!("%{}-{}%", self.evaluator.placeholder_prefix, txn.len() - 1)
     }
 
-    pub fn insert_refdef(&self, refid: String, refdef: RefDef) {
-        self.evaluator
-            .refdefs
-            .write()
-            .unwrap()
-            .insert(refid, refdef);
+    /// Remove the placeholders from text, leaving only what is known before linking.
+    pub fn strip_placeholders<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        self.evaluator.placeholder_pattern.replace_all(text, "")
     }
 
-    pub fn add_to_toctree(&self, slug: Slug, title: Option<String>) {
+    pub fn insert_refdef(&self, node: &Node, refid: String, mut refdef: RefDef) {
+        refdef.location = Some(self.get_location(node));
+
+        let mut refdefs = self.evaluator.refdefs.write().unwrap();
+        let mut slot = match refdefs.entry(refid) {
+            Entry::Vacant(slot) => {
+                slot.insert(refdef);
+                return;
+            }
+            Entry::Occupied(slot) => slot,
+        };
+
+        // Resolve the conflict the same way regardless of which page was compiled first
+        match (slot.get().is_override, refdef.is_override) {
+            (false, true) => {
+                slot.insert(refdef);
+            }
+            (true, false) => (),
+            _ => {
+                let previous = match slot.get().location {
+                    Some(ref location) => location.to_string(),
+                    None => "?".to_owned(),
+                };
+
+                self.error(
+                    node,
+                    &format!(
+                        "Duplicate reference '{}'\n  previously defined at {}",
+                        slot.key(),
+                        previous
+                    ),
+                );
+            }
+        }
+    }
+
+    pub fn is_external_project(&self, name: &str) -> bool {
+        self.evaluator.external_projects.contains_key(name)
+    }
+
+    pub fn matches_tags(&self, expression: &TagExpression) -> bool {
+        expression.matches(&self.evaluator.tags)
+    }
+
+    pub fn add_redirect(&self, node: &Node, from: Slug) {
+        self.evaluator.redirects.write().unwrap().push(Redirect {
+            from,
+            to: self.get_slug().to_owned(),
+            location: Some(self.get_location(node)),
+        });
+    }
+
+    /// Add an entry below the current page in the named toctree, or in the main tree if no
+    /// name is given.
+    pub fn add_to_toctree(
+        &self,
+        node: &Node,
+        tree: Option<&str>,
+        element: TocTreeElement,
+    ) -> Result<(), ()> {
         let current_slug = self.current_slug.as_ref().unwrap();
-        self.evaluator
-            .toctree
-            .write()
-            .unwrap()
-            .add(current_slug, slug, title);
+        let element = element.with_location(self.get_location(node));
+
+        let name = match tree {
+            Some(name) if name != MAIN_TOCTREE => name,
+            _ => {
+                self.evaluator
+                    .toctree
+                    .write()
+                    .unwrap()
+                    .add(current_slug, element);
+                return Ok(());
+            }
+        };
+
+        let mut named_toctrees = self.evaluator.named_toctrees.write().unwrap();
+        if let Some(toctree) = named_toctrees.get_mut(name) {
+            toctree.add(current_slug, element);
+            return Ok(());
+        }
+
+        let names = named_toctrees
+            .keys()
+            .map(|name| name.as_str())
+            .chain(Some(MAIN_TOCTREE));
+        match suggest::suggest(name, names) {
+            Some(suggestion) => self.error(
+                node,
+                &format!("Unknown toctree '{}'; did you mean '{}'?", name, suggestion),
+            ),
+            None => self.error(node, &format!("Unknown toctree '{}'", name)),
+        }
+
+        Err(())
     }
 
     pub fn handle_heading(&mut self, level: i8) -> Result<String, ()> {
@@ -266,6 +723,42 @@ impl<'a> Worker<'a> {
         Ok(prefix)
     }
 
+    /// Return the number of a new heading within the current page, such as "2.1" for the
+    /// first level-3 heading of the second level-2 section, or None if section numbering is
+    /// disabled. Level-1 headings share the page's own number, and so are numbered "".
+    pub fn next_section_number(&mut self, level: i8) -> Option<String> {
+        if !self.evaluator.section_numbers {
+            return None;
+        }
+
+        let depth = cmp::max(level - 1, 0) as usize;
+        self.section_counters.resize(depth, 0);
+        if let Some(counter) = self.section_counters.last_mut() {
+            *counter += 1;
+        }
+
+        let numbers: Vec<String> = self.section_counters.iter().map(|n| n.to_string()).collect();
+        Some(numbers.join("."))
+    }
+
+    /// Reserve an element ID on the current page, adding a numeric suffix if it is taken.
+    pub fn claim_id(&mut self, id: &str) -> String {
+        let mut candidate = id.to_owned();
+        let mut n = 0;
+        while self.used_ids.contains(&candidate) {
+            n += 1;
+            candidate = format!("{}-{}", id, n);
+        }
+
+        self.used_ids.insert(candidate.to_owned());
+        candidate
+    }
+
+    /// Return true if a heading has been emitted on the current page.
+    pub fn in_section(&self) -> bool {
+        self.current_level > 0
+    }
+
     pub fn close_sections(&self) -> String {
         "</section>".repeat(self.current_level as usize)
     }
@@ -285,19 +778,16 @@ impl<'a> Worker<'a> {
         }
     }
 
-    pub fn log(&self, node: &Node, message: &str, level: log::Level) {
+    pub fn get_location(&self, node: &Node) -> SourceLocation {
         let file_path = self.parser.get_node_source_path(node);
-        log!(
-            level,
-            "{}\n  --> {}:{}:?",
-            message,
-            file_path.unwrap_or_else(|| Path::new("")).to_string_lossy(),
-            if node.lineno >= 0 {
-                node.lineno.to_string()
-            } else {
-                "?".to_owned()
-            }
-        );
+        SourceLocation {
+            path: file_path.unwrap_or_else(|| Path::new("")).to_owned(),
+            lineno: node.lineno,
+        }
+    }
+
+    pub fn log(&self, node: &Node, message: &str, level: log::Level) {
+        log!(level, "{}\n  --> {}:?", message, self.get_location(node));
     }
 
     #[allow(dead_code)]
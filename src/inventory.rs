@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use serde_json;
+use evaluator::RefDef;
+
+/// A reference target as exported to other projects.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryEntry {
+    pub id: String,
+    pub title: String,
+    pub slug: String,
+    pub anchor: Option<String>,
+
+    /// The page's URL relative to the project's root.
+    pub path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Inventory {
+    pub refdefs: Vec<InventoryEntry>,
+}
+
+impl Inventory {
+    pub fn from_refdefs(refdefs: &HashMap<String, RefDef>, pretty_url: bool) -> Self {
+        let mut entries: Vec<_> = refdefs
+            .iter()
+            .map(|(id, refdef)| InventoryEntry {
+                id: id.to_owned(),
+                title: refdef.title.to_owned(),
+                slug: refdef.slug.as_ref().to_owned(),
+                anchor: refdef.fragment.to_owned(),
+                path: refdef.slug.link_path(pretty_url),
+            })
+            .collect();
+
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
+        Inventory { refdefs: entries }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let mut data = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut data))
+            .map_err(|err| err.to_string())?;
+
+        serde_json::from_str(&data).map_err(|err| err.to_string())
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let data = serde_json::to_string(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(data.as_bytes())
+    }
+}
+
+/// Another Rocket project whose references can be linked to as "project:id".
+pub struct ExternalProject {
+    base_url: String,
+    refdefs: HashMap<String, InventoryEntry>,
+}
+
+impl ExternalProject {
+    pub fn new(base_url: &str, inventory: Inventory) -> Self {
+        ExternalProject {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            refdefs: inventory
+                .refdefs
+                .into_iter()
+                .map(|entry| (entry.id.to_owned(), entry))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, refid: &str) -> Option<&InventoryEntry> {
+        self.refdefs.get(refid)
+    }
+
+    /// Return the absolute URL of a reference in this project.
+    pub fn url(&self, entry: &InventoryEntry) -> String {
+        match entry.anchor {
+            Some(ref anchor) => format!("{}/{}#{}", self.base_url, entry.path, anchor),
+            None => format!("{}/{}", self.base_url, entry.path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use page::Slug;
+
+    #[test]
+    fn test_round_trip() {
+        let mut refdefs = HashMap::new();
+        refdefs.insert(
+            "ref-install".to_owned(),
+            RefDef::new("Installation", &Slug::new("tutorials/installation".to_owned())),
+        );
+        refdefs.insert(
+            "term-BSON".to_owned(),
+            RefDef::new("BSON", &Slug::new("index".to_owned())).with_fragment("term-BSON"),
+        );
+
+        let inventory = Inventory::from_refdefs(&refdefs, true);
+        let data = serde_json::to_string(&inventory).unwrap();
+        let inventory: Inventory = serde_json::from_str(&data).unwrap();
+        assert_eq!(inventory.refdefs[0].id, "ref-install".to_owned());
+
+        let project = ExternalProject::new("https://example.com/docs/", inventory);
+        let entry = project.get("ref-install").unwrap();
+        assert_eq!(
+            project.url(entry),
+            "https://example.com/docs/tutorials/installation".to_owned()
+        );
+
+        let entry = project.get("term-BSON").unwrap();
+        assert_eq!(
+            project.url(entry),
+            "https://example.com/docs/#term-BSON".to_owned()
+        );
+        assert!(project.get("ref-missing").is_none());
+    }
+}
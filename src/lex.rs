@@ -10,6 +10,7 @@ lazy_static! {
         | (?:\n+\x20+)
         | "
         | =
+        | :
         | \s+
         | [^\(\):=\s"]+"#).expect("Failed to compile lexer regex");
 }
@@ -247,4 +248,24 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_colon() {
+        assert_eq!(
+            lex("(:ref server:a:) 10:30"),
+            vec![
+                Token::StartBlock(0, 0),
+                Token::Text(0, "ref"),
+                Token::Text(0, " "),
+                Token::Text(0, "server"),
+                Token::Text(0, ":"),
+                Token::Text(0, "a"),
+                Token::RightParen(0),
+                Token::Text(0, " "),
+                Token::Text(0, "10"),
+                Token::Text(0, ":"),
+                Token::Text(0, "30"),
+            ]
+        );
+    }
 }
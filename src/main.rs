@@ -26,26 +26,35 @@ mod evaluator;
 mod highlighter;
 mod init;
 mod inject_paragraphs;
+mod inventory;
 mod lex;
 mod page;
 mod parse;
+mod search;
+mod sitemap;
+mod suggest;
+mod tags;
 mod theme;
 mod toctree;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::From;
 use std::fs::{self, File};
 use std::io::{self, Read, Write};
 use std::ops::DerefMut;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 use std::{env, mem, process};
-use evaluator::{Evaluator, Worker};
+use evaluator::{Evaluator, Redirect, Worker};
 use inject_paragraphs::inject_paragraphs;
-use page::{Page, Slug};
-use toctree::TocTree;
-use directives::{glossary, logic};
+use inventory::{ExternalProject, Inventory};
+use page::{Page, Slug, UrlConfig, UrlStyle};
+use search::{SearchDocument, SearchIndex};
+use sitemap::Sitemap;
+use toctree::{TocTree, MAIN_TOCTREE};
+use directives::{data, glossary, logic, string};
+use directives::logic::{Comparison, Operator};
 use scoped_threadpool::Pool;
 
 #[derive(Debug)]
@@ -75,6 +84,21 @@ struct RawConfig {
     output: Option<PathBuf>,
     templates: HashMap<String, String>,
     theme_constants: Option<serde_json::map::Map<String, serde_json::Value>>,
+    tags: Option<Vec<String>>,
+    heading_ids: Option<directives::HeadingIds>,
+    inventories: Option<HashMap<String, RawInventory>>,
+    url_style: Option<UrlStyle>,
+    base_url: Option<String>,
+    redirects: Option<HashMap<String, String>>,
+    section_numbers: Option<bool>,
+    search_terms: Option<bool>,
+    toctrees: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize)]
+struct RawInventory {
+    path: PathBuf,
+    base_url: String,
 }
 
 struct Project {
@@ -85,8 +109,14 @@ struct Project {
     templates: Vec<(glob::Pattern, String)>,
     theme_constants: serde_json::map::Map<String, serde_json::Value>,
     syntax_theme: String,
-
-    pretty_url: bool,
+    tags: Vec<String>,
+    heading_ids: directives::HeadingIds,
+    inventories: Vec<(String, PathBuf, String)>,
+    urls: UrlConfig,
+    redirects: Vec<(Slug, Slug)>,
+    section_numbers: bool,
+    search_terms: bool,
+    toctrees: Vec<(String, Slug)>,
 }
 
 impl Project {
@@ -128,7 +158,38 @@ impl Project {
                 .theme_constants
                 .unwrap_or_else(serde_json::map::Map::new),
             syntax_theme,
-            pretty_url: true,
+            tags: config.tags.unwrap_or_default(),
+            heading_ids: config
+                .heading_ids
+                .unwrap_or(directives::HeadingIds::Unicode),
+            inventories: config
+                .inventories
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(name, inventory)| (name, inventory.path, inventory.base_url))
+                .collect(),
+            urls: UrlConfig::new(
+                config.url_style.unwrap_or(UrlStyle::Pretty),
+                config.base_url.as_deref(),
+            ),
+            redirects: config
+                .redirects
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(from, to)| {
+                    let from = Slug::new(from.trim_matches('/').to_owned());
+                    let to = Slug::new(to.trim_matches('/').to_owned());
+                    (from, to)
+                })
+                .collect(),
+            section_numbers: config.section_numbers.unwrap_or(false),
+            search_terms: config.search_terms.unwrap_or(false),
+            toctrees: config
+                .toctrees
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(name, root)| (name, Slug::new(root.trim_matches('/').to_owned())))
+                .collect(),
         })
     }
 
@@ -152,17 +213,21 @@ impl Project {
             slug: worker.get_slug().clone(),
             body: output,
             theme_config: worker.theme_config.clone(),
+            headings: worker.headings.clone(),
+            orphan: worker.orphan,
         };
 
         Ok(page)
     }
 
+    /// Substitute a page's references and write it out through its template, returning
+    /// the page's linked body.
     fn link_file(
         &self,
         evaluator: &Evaluator,
         page: &Page,
         renderer: &theme::Renderer,
-    ) -> Result<(), LinkError> {
+    ) -> Result<String, LinkError> {
         debug!("Linking {}", &page.slug);
 
         // Find the template that matches this path
@@ -180,14 +245,105 @@ impl Project {
         };
 
         let rendered = renderer.render(template_name, &self.theme_constants, page, &new_body)?;
-        let output_path = page.slug.create_output_path(&self.output, self.pretty_url);
+        let output_path = page.slug.create_output_path(&self.output, self.urls.pretty_url);
         let output_dir = output_path.parent().expect("Couldn't get output directory");
 
         fs::create_dir_all(output_dir)?;
         let mut file = File::create(&output_path)?;
         file.write_all(rendered.as_bytes())?;
 
-        Ok(())
+        Ok(new_body)
+    }
+
+    /// List every page in a sitemap, except for those that opt out by setting the
+    /// "sitemap" theme-config value to false. Sitemaps need absolute URLs, so this returns
+    /// None unless a base URL is configured.
+    fn build_sitemap(&self, pages: &[Page]) -> Option<Sitemap> {
+        let base_url = self.urls.base_url.as_ref()?;
+        let mut sitemap = Sitemap::new();
+
+        for page in pages {
+            if !page.theme_config_enabled("sitemap") {
+                continue;
+            }
+
+            let url = format!("{}/{}", base_url, page.slug.link_path(self.urls.pretty_url));
+            let modified = fs::metadata(&page.source_path).and_then(|m| m.modified()).ok();
+            sitemap.add(url, modified);
+        }
+
+        Some(sitemap)
+    }
+
+    /// Return the output path and contents of the page written at each redirect's former
+    /// location. Redirects that collide with a page, point to an unknown page, or repeat an
+    /// earlier source are skipped with a warning.
+    fn redirect_pages(&self, evaluator: &Evaluator) -> Vec<(PathBuf, String)> {
+        let titles = evaluator.titles.read().unwrap();
+        let redirects = evaluator.redirects.read().unwrap();
+
+        // Redirects from config.toml come first, so they win over any duplicates
+        let mut redirects: Vec<_> = redirects.iter().collect();
+        redirects.sort_by_key(|r| (r.from.as_ref(), r.location.is_some(), r.to.as_ref()));
+
+        let mut written = HashSet::new();
+        let mut pages = vec![];
+        for redirect in redirects {
+            let declared_at = match redirect.location {
+                Some(ref location) => location.to_string(),
+                None => "config.toml".to_owned(),
+            };
+
+            if titles.contains_key(&redirect.from) {
+                warn!(
+                    "Redirect from '{}' collides with an existing page\n  --> {}",
+                    redirect.from, declared_at
+                );
+                continue;
+            }
+
+            if !titles.contains_key(&redirect.to) {
+                warn!(
+                    "Redirect to unknown page '{}'\n  --> {}",
+                    redirect.to, declared_at
+                );
+                continue;
+            }
+
+            if !written.insert(&redirect.from) {
+                warn!(
+                    "Duplicate redirect from '{}'\n  --> {}",
+                    redirect.from, declared_at
+                );
+                continue;
+            }
+
+            let url = self.urls.page_url_from(&redirect.from, &redirect.to);
+            let output_path = redirect
+                .from
+                .create_output_path(&self.output, self.urls.pretty_url);
+            pages.push((output_path, theme::render_redirect(&url)));
+        }
+
+        pages
+    }
+
+    fn write_redirects(&self, evaluator: &Evaluator) {
+        for (output_path, contents) in self.redirect_pages(evaluator) {
+            let result = output_path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| File::create(&output_path))
+                .and_then(|mut file| file.write_all(contents.as_bytes()));
+
+            if let Err(err) = result {
+                error!(
+                    "Failed to write redirect '{}': {}",
+                    output_path.to_string_lossy(),
+                    err
+                );
+            }
+        }
     }
 }
 
@@ -195,8 +351,8 @@ fn build_project(project: Project, evaluator: Evaluator) {
     let num_cpus = num_cpus::get();
     let project = Arc::new(project);
     let evaluator = Arc::new(evaluator);
-    let titles: Arc<Mutex<HashMap<Slug, String>>> = Arc::new(Mutex::new(HashMap::new()));
     let pending_pages: Arc<Mutex<Vec<Page>>> = Arc::new(Mutex::new(vec![]));
+    let excluded_pages: Arc<Mutex<HashSet<Slug>>> = Arc::new(Mutex::new(HashSet::new()));
 
     debug!("Crawling source directory");
 
@@ -226,8 +382,8 @@ fn build_project(project: Project, evaluator: Evaluator) {
     for chunk in chunks {
         let project = Arc::clone(&project);
         let evaluator = Arc::clone(&evaluator);
-        let titles = Arc::clone(&titles);
         let pending_pages = Arc::clone(&pending_pages);
+        let excluded_pages = Arc::clone(&excluded_pages);
 
         let thread = std::thread::spawn(move || {
             let mut worker = Worker::new_with_options(&evaluator, &project.syntax_theme);
@@ -241,9 +397,14 @@ fn build_project(project: Project, evaluator: Evaluator) {
                 worker.set_slug(slug);
 
                 match project.build_file(&mut worker, &path) {
+                    Ok(ref page) if worker.excluded => {
+                        debug!("Skipping {}, which doesn't match the build's tags", page.slug);
+                        excluded_pages.lock().unwrap().insert(page.slug.to_owned());
+                    }
                     Ok(page) => {
-                        titles
-                            .lock()
+                        evaluator
+                            .titles
+                            .write()
                             .unwrap()
                             .insert(page.slug.to_owned(), page.title());
                         pending_pages.lock().unwrap().push(page);
@@ -264,21 +425,69 @@ fn build_project(project: Project, evaluator: Evaluator) {
             .expect("At least one compilation worker panicked");
     }
 
+    evaluator.remove_pages(&excluded_pages.lock().unwrap());
+
     let mut toctree = {
         let mut txn = evaluator.toctree.write().unwrap();
         mem::replace(txn.deref_mut(), TocTree::new_empty())
     };
 
-    toctree.finish(titles.lock().unwrap().deref());
+    let mut named_toctrees = mem::take(evaluator.named_toctrees.write().unwrap().deref_mut());
+
+    {
+        let titles = evaluator.titles.read().unwrap();
+        let mut names: Vec<_> = named_toctrees.keys().cloned().collect();
+        names.sort();
+
+        let mut messages = toctree.finish(titles.deref());
+        messages.extend(toctree.check_root(MAIN_TOCTREE));
+        for name in names {
+            let named_toctree = named_toctrees.get_mut(&name).unwrap();
+            messages.extend(named_toctree.finish(titles.deref()));
+            messages.extend(named_toctree.check_root(&name));
+        }
+
+        for message in messages {
+            evaluator.report_error(&message);
+        }
+    }
+
+    *evaluator.page_numbers.write().unwrap() = toctree.section_numbers().clone();
+
+    // Without a root, no page is reachable, so there is no point in warning about each one
+    if toctree.check_root(MAIN_TOCTREE).is_none() {
+        let mut pending_pages = pending_pages.lock().unwrap();
+        pending_pages.sort_by(|a, b| a.slug.as_ref().cmp(b.slug.as_ref()));
+        for page in pending_pages.iter() {
+            let included = toctree.contains(&page.slug)
+                || named_toctrees.values().any(|tree| tree.contains(&page.slug));
+            if !page.orphan && !included {
+                warn!(
+                    "Page '{}' is not included in any toctree\n  --> {}",
+                    page.slug,
+                    page.source_path.display()
+                );
+            }
+        }
+    }
+
+    let sitemap = project.build_sitemap(pending_pages.lock().unwrap().as_slice());
 
     let theme = theme::Theme::load(&project.theme).expect("Failed to load theme");
 
     let renderer = Arc::new(
-        theme::Renderer::new(theme, &Arc::new(toctree)).expect("Failed to construct renderer"),
+        theme::Renderer::new(
+            theme,
+            &Arc::new(toctree),
+            &Arc::new(named_toctrees),
+            evaluator.backlinks(),
+            project.urls.clone(),
+        ).expect("Failed to construct renderer"),
     );
 
     debug!("Linking with {} workers", num_cpus);
 
+    let search_documents = Mutex::new(vec![]);
     let mut pool = Pool::new(num_cpus as u32);
     pool.scoped(|scoped| {
         let mut pending_pages = pending_pages.lock().unwrap();
@@ -286,15 +495,49 @@ fn build_project(project: Project, evaluator: Evaluator) {
             let project = Arc::clone(&project);
             let evaluator = Arc::clone(&evaluator);
             let renderer = Arc::clone(&renderer);
+            let search_documents = &search_documents;
 
             scoped.execute(move || {
-                project
+                let mut page = page;
+                page.headings = evaluator.link_headings(&page);
+
+                let body = project
                     .link_file(&evaluator, &page, &renderer)
                     .expect("Failed to link page");
+
+                if let Some(document) = SearchDocument::from_page(&evaluator, &page, &body) {
+                    search_documents.lock().unwrap().push(document);
+                }
             });
         }
     });
 
+    project.write_redirects(&evaluator);
+
+    let inventory = {
+        let refdefs = evaluator.refdefs.read().unwrap();
+        Inventory::from_refdefs(refdefs.deref(), project.urls.pretty_url)
+    };
+
+    if let Err(err) = inventory.write(&project.output.join("inventory.json")) {
+        error!("Failed to write inventory: {}", err);
+    }
+
+    let search_documents = search_documents.into_inner().unwrap();
+    let search_index = SearchIndex::new(search_documents, project.search_terms);
+    if let Err(err) = search_index.write(&project.output.join("search.json")) {
+        error!("Failed to write search index: {}", err);
+    }
+
+    match sitemap {
+        Some(sitemap) => {
+            if let Err(err) = sitemap.write(&project.output.join("sitemap.xml")) {
+                error!("Failed to write sitemap: {}", err);
+            }
+        }
+        None => debug!("Not writing sitemap.xml, since base_url is not set"),
+    }
+
     let n_errors = evaluator.get_num_errors();
     if n_errors > 0 {
         info!("{} errors", n_errors);
@@ -302,13 +545,48 @@ fn build_project(project: Project, evaluator: Evaluator) {
     }
 }
 
-fn build(verbose: bool) {
+fn build(verbose: bool, tags: Vec<String>) {
     let mut config =
         Project::read_toml(Path::new("config.toml")).expect("Failed to open config.toml");
 
     config.verbose = verbose;
+    config.tags.extend(tags);
+
+    let mut evaluator =
+        Evaluator::new_with_options(config.content_dir.to_owned(), config.urls.clone());
+    evaluator.tags = config.tags.iter().cloned().collect();
+    for (name, root) in &config.toctrees {
+        evaluator.add_toctree(name, root.to_owned());
+    }
+
+    if config.section_numbers {
+        evaluator.enable_section_numbers();
+    }
+
+    evaluator.redirects = RwLock::new(
+        config
+            .redirects
+            .iter()
+            .map(|(from, to)| Redirect {
+                from: from.to_owned(),
+                to: to.to_owned(),
+                location: None,
+            })
+            .collect(),
+    );
 
-    let mut evaluator = Evaluator::new_with_options(config.content_dir.to_owned());
+    for (name, path, base_url) in &config.inventories {
+        match Inventory::load(path) {
+            Ok(inventory) => {
+                let project = ExternalProject::new(base_url, inventory);
+                evaluator.external_projects.insert(name.to_owned(), project);
+            }
+            Err(msg) => {
+                error!("Failed to load inventory '{}': {}", path.to_string_lossy(), msg);
+                process::exit(1);
+            }
+        }
+    }
     evaluator.register_prelude("code", Box::new(directives::Code));
     evaluator.register_prelude("table", Box::new(directives::Dummy));
     evaluator.register_prelude("version", Box::new(directives::Version::new("3.4.0")));
@@ -325,7 +603,9 @@ fn build(verbose: bool) {
     evaluator.register_prelude("concat", Box::new(directives::Concat));
     evaluator.register_prelude("include", Box::new(directives::Include));
     evaluator.register_prelude("import", Box::new(directives::Import));
+    evaluator.register_prelude("load-data", Box::new(data::LoadData));
     evaluator.register_prelude("null", Box::new(directives::Dummy));
+    evaluator.register_prelude("only", Box::new(directives::Only));
     evaluator.register_prelude("let", Box::new(directives::Let));
     evaluator.register_prelude("define", Box::new(directives::Define));
     evaluator.register_prelude("theme-config", Box::new(directives::ThemeConfig));
@@ -334,7 +614,11 @@ fn build(verbose: bool) {
     evaluator.register_prelude("ref", Box::new(directives::RefDirective::new("ref")));
     evaluator.register_prelude("define-program", Box::new(directives::RefDefDirective::new("program")));
     evaluator.register_prelude("program", Box::new(directives::RefDirective::new("program")));
+    evaluator.register_prelude("doc", Box::new(directives::Doc));
     evaluator.register_prelude("link", Box::new(directives::Link));
+    evaluator.register_prelude("redirect-from", Box::new(directives::RedirectFrom));
+    evaluator.register_prelude("orphan", Box::new(directives::Orphan));
+    evaluator.register_prelude("page-only", Box::new(directives::PageOnly));
     evaluator.register_prelude("figure", Box::new(directives::Figure));
     evaluator.register_prelude("ul", Box::new(directives::List::new("ul")));
     evaluator.register_prelude("ol", Box::new(directives::List::new("ol")));
@@ -349,18 +633,68 @@ fn build(verbose: bool) {
     evaluator.register_prelude("__", Box::new(directives::FormattingMarker::new("em")));
 
     // Headers
-    evaluator.register_prelude("h1", Box::new(directives::Heading::new(1)));
-    evaluator.register_prelude("h2", Box::new(directives::Heading::new(2)));
-    evaluator.register_prelude("h3", Box::new(directives::Heading::new(3)));
-    evaluator.register_prelude("h4", Box::new(directives::Heading::new(4)));
-    evaluator.register_prelude("h5", Box::new(directives::Heading::new(5)));
-    evaluator.register_prelude("h6", Box::new(directives::Heading::new(6)));
+    for level in 1..7 {
+        evaluator.register_prelude(
+            format!("h{}", level),
+            Box::new(directives::Heading::new_with_options(level, config.heading_ids)),
+        );
+    }
 
     // Logic operations
     evaluator.register_prelude("if", Box::new(logic::If));
     evaluator.register_prelude("not", Box::new(logic::Not));
+    evaluator.register_prelude("and", Box::new(logic::And));
+    evaluator.register_prelude("or", Box::new(logic::Or));
+    evaluator.register_prelude("cond", Box::new(logic::Cond));
+    evaluator.register_prelude("switch", Box::new(logic::Switch));
     evaluator.register_prelude("=", Box::new(logic::Equals));
     evaluator.register_prelude("!=", Box::new(logic::NotEquals));
+    evaluator.register_prelude("<", Box::new(logic::NumericCompare::new(Comparison::Less)));
+    evaluator.register_prelude(
+        "<=",
+        Box::new(logic::NumericCompare::new(Comparison::LessOrEqual)),
+    );
+    evaluator.register_prelude(">", Box::new(logic::NumericCompare::new(Comparison::Greater)));
+    evaluator.register_prelude(
+        ">=",
+        Box::new(logic::NumericCompare::new(Comparison::GreaterOrEqual)),
+    );
+    evaluator.register_prelude(
+        "version=",
+        Box::new(logic::VersionCompare::new(Comparison::Equal)),
+    );
+    evaluator.register_prelude(
+        "version<",
+        Box::new(logic::VersionCompare::new(Comparison::Less)),
+    );
+    evaluator.register_prelude(
+        "version<=",
+        Box::new(logic::VersionCompare::new(Comparison::LessOrEqual)),
+    );
+    evaluator.register_prelude(
+        "version>",
+        Box::new(logic::VersionCompare::new(Comparison::Greater)),
+    );
+    evaluator.register_prelude(
+        "version>=",
+        Box::new(logic::VersionCompare::new(Comparison::GreaterOrEqual)),
+    );
+
+    // Arithmetic
+    evaluator.register_prelude("+", Box::new(logic::Arithmetic::new(Operator::Add)));
+    evaluator.register_prelude("-", Box::new(logic::Arithmetic::new(Operator::Subtract)));
+    evaluator.register_prelude("*", Box::new(logic::Arithmetic::new(Operator::Multiply)));
+    evaluator.register_prelude("/", Box::new(logic::Arithmetic::new(Operator::Divide)));
+    evaluator.register_prelude("%", Box::new(logic::Arithmetic::new(Operator::Modulo)));
+
+    // String operations
+    evaluator.register_prelude("upper", Box::new(string::Upper));
+    evaluator.register_prelude("lower", Box::new(string::Lower));
+    evaluator.register_prelude("trim", Box::new(string::Trim));
+    evaluator.register_prelude("replace", Box::new(string::Replace));
+    evaluator.register_prelude("match", Box::new(string::Match));
+    evaluator.register_prelude("split", Box::new(string::Split));
+    evaluator.register_prelude("join", Box::new(string::Join));
 
     let start_time = time::precise_time_ns();
     build_project(config, evaluator);
@@ -374,6 +708,7 @@ fn build(verbose: bool) {
 const DESCRIPTION_BUILD: &str = "Build the Rocket project in the current working directory.";
 const DESCRIPTION_NEW: &str = "Create an empty Rocket project.";
 const HELP_VERBOSE: &str = "Increase logging verbosity.";
+const HELP_TAG: &str = "Enable a build tag for (:only:) blocks. May be given multiple times.";
 
 enum ArgMode {
     Root,
@@ -385,6 +720,8 @@ fn main() {
     let args = env::args().skip(1);
     let mut verbose = false;
     let mut new_name: Option<String> = None;
+    let mut tags: Vec<String> = vec![];
+    let mut expecting_tag = false;
     let mut mode = ArgMode::Root;
 
     let help = |code| -> ! {
@@ -407,6 +744,7 @@ fn main() {
         println!("Description:\n  {}\n", DESCRIPTION_BUILD);
         println!("Optional arguments:");
         println!("  --verbose, -v\n    {}\n", HELP_VERBOSE);
+        println!("  --tag, -t <tag>\n    {}\n", HELP_TAG);
         println!("  --help, -h\n    Print this message and exit.\n");
 
         process::exit(code);
@@ -424,6 +762,12 @@ fn main() {
     };
 
     for arg in args {
+        if expecting_tag {
+            tags.push(arg);
+            expecting_tag = false;
+            continue;
+        }
+
         match mode {
             ArgMode::Root => match arg.as_ref() {
                 "-h" | "--help" => help(0),
@@ -452,11 +796,16 @@ fn main() {
             ArgMode::Build => match arg.as_ref() {
                 "-h" | "--help" => help_build(0),
                 "-v" | "--verbose" => verbose = true,
+                "-t" | "--tag" => expecting_tag = true,
                 _ => help_build(1),
             },
         }
     }
 
+    if expecting_tag {
+        help_build(1);
+    }
+
     let logging_filter = if verbose {
         log::LevelFilter::Debug
     } else {
@@ -468,6 +817,74 @@ fn main() {
     match mode {
         ArgMode::Root => help(1),
         ArgMode::New => init::init(&new_name.unwrap_or_else(|| help_new(1))),
-        ArgMode::Build => build(verbose),
+        ArgMode::Build => build(verbose, tags),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evaluator::SourceLocation;
+
+    fn redirect_project(style: UrlStyle) -> (Project, Evaluator) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test/redirects.toml");
+        let mut project = Project::read_toml(&path).unwrap();
+        project.urls = UrlConfig::new(style, None);
+
+        let mut evaluator = Evaluator::new_with_options(PathBuf::new(), project.urls.clone());
+        for slug in &["index", "reference", "tutorials/installation", "tutorials/other"] {
+            let title = slug.to_string();
+            evaluator.titles.get_mut().unwrap().insert(Slug::new(title.clone()), title);
+        }
+
+        let mut redirects: Vec<_> = project
+            .redirects
+            .iter()
+            .map(|(from, to)| Redirect {
+                from: from.to_owned(),
+                to: to.to_owned(),
+                location: None,
+            })
+            .collect();
+
+        // The config.toml redirect from the same source wins over this one
+        redirects.push(Redirect {
+            from: Slug::new("install".to_owned()),
+            to: Slug::new("tutorials/other".to_owned()),
+            location: Some(SourceLocation {
+                path: PathBuf::from("tutorials/other.rocket"),
+                lineno: 3,
+            }),
+        });
+
+        evaluator.redirects = RwLock::new(redirects);
+        (project, evaluator)
+    }
+
+    #[test]
+    fn test_redirect_pages() {
+        let (project, evaluator) = redirect_project(UrlStyle::Pretty);
+        let pages = project.redirect_pages(&evaluator);
+        let paths: Vec<_> = pages.iter().map(|(path, _)| path.to_owned()).collect();
+
+        // "reference" is an existing page and "missing" doesn't exist, so both are skipped
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("build/install/index.html"),
+                PathBuf::from("build/tutorials/setup/index.html"),
+            ]
+        );
+
+        assert!(pages[0].1.contains("url=../tutorials/installation\""));
+        assert!(pages[0].1.contains("<a href=\"../tutorials/installation\">"));
+        assert!(pages[1].1.contains("url=../../tutorials/installation\""));
+
+        let (project, evaluator) = redirect_project(UrlStyle::Flat);
+        let pages = project.redirect_pages(&evaluator);
+        assert_eq!(pages[0].0, PathBuf::from("build/install.html"));
+        assert!(pages[0].1.contains("url=tutorials/installation.html\""));
+        assert_eq!(pages[1].0, PathBuf::from("build/tutorials/setup.html"));
+        assert!(pages[1].1.contains("url=../tutorials/installation.html\""));
     }
 }
@@ -3,6 +3,54 @@ use std::fmt;
 use std::path::{Path, PathBuf};
 use serde_json::{self, Value};
 
+/// How pages are laid out in the output directory.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UrlStyle {
+    /// "foo/bar" is written to "foo/bar/index.html" and linked as "foo/bar".
+    Pretty,
+
+    /// "foo/bar" is written to "foo/bar.html" and linked as "foo/bar.html".
+    Flat,
+}
+
+/// The project-wide settings used to build every internal link.
+#[derive(Debug, Clone)]
+pub struct UrlConfig {
+    pub pretty_url: bool,
+
+    /// If set, links are absolute URLs under this prefix instead of relative paths.
+    pub base_url: Option<String>,
+}
+
+impl UrlConfig {
+    pub fn new(style: UrlStyle, base_url: Option<&str>) -> Self {
+        UrlConfig {
+            pretty_url: style == UrlStyle::Pretty,
+            base_url: base_url.map(|url| url.trim_end_matches('/').to_owned()),
+        }
+    }
+
+    /// Return the URL of a path relative to the output root, as seen from the given page.
+    pub fn url_from(&self, source: &Slug, path: &str) -> String {
+        match self.base_url {
+            Some(ref base_url) => format!("{}/{}", base_url, path),
+            None => source.path_to(path, self.pretty_url),
+        }
+    }
+
+    /// Return the URL of a page, as seen from the given page.
+    pub fn page_url_from(&self, source: &Slug, dest: &Slug) -> String {
+        self.url_from(source, &dest.link_path(self.pretty_url))
+    }
+}
+
+impl Default for UrlConfig {
+    fn default() -> Self {
+        Self::new(UrlStyle::Pretty, None)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Slug {
     slug: String,
@@ -32,6 +80,17 @@ impl Slug {
         self.slug.matches('/').count() + modifier
     }
 
+    /// Return the URL of this page relative to the output root.
+    pub fn link_path(&self, pretty_url: bool) -> String {
+        if !pretty_url {
+            format!("{}.html", self.slug)
+        } else if self.slug == "index" {
+            "".to_owned()
+        } else {
+            self.slug.to_owned()
+        }
+    }
+
     pub fn path_to(&self, dest: &str, pretty_url: bool) -> String {
         let slug_prefix = "../".repeat(self.depth(pretty_url));
         format!("{}{}", slug_prefix, dest)
@@ -50,14 +109,65 @@ impl fmt::Display for Slug {
     }
 }
 
+/// A heading as it appears in a page's outline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageHeading {
+    pub level: i8,
+    pub id: String,
+    pub title: String,
+}
+
+/// Nest a flat list of headings under their closest preceding heading of a lower level.
+fn nest_headings(headings: &[PageHeading]) -> Vec<Value> {
+    let mut result = vec![];
+    let mut i = 0;
+    while i < headings.len() {
+        let heading = &headings[i];
+        let end = headings[i + 1..]
+            .iter()
+            .position(|h| h.level <= heading.level)
+            .map_or(headings.len(), |n| i + 1 + n);
+
+        result.push(json!({
+            "level": heading.level,
+            "id": heading.id,
+            "title": heading.title,
+            "url": format!("#{}", heading.id),
+            "children": nest_headings(&headings[i + 1..end]),
+        }));
+        i = end;
+    }
+
+    result
+}
+
 pub struct Page {
     pub source_path: PathBuf,
     pub slug: Slug,
     pub body: String,
     pub theme_config: serde_json::map::Map<String, Value>,
+
+    /// Every heading on the page, in document order.
+    pub headings: Vec<PageHeading>,
+
+    /// True if the page is deliberately left out of the toctree.
+    pub orphan: bool,
 }
 
 impl Page {
+    /// Return a page with the given slug and body, and nothing else set.
+    #[cfg(test)]
+    pub fn new_for_test(slug: &str, body: &str) -> Self {
+        Page {
+            source_path: PathBuf::from(format!("{}.rocket", slug)),
+            slug: Slug::new(slug.to_owned()),
+            body: body.to_owned(),
+            theme_config: serde_json::map::Map::new(),
+            headings: vec![],
+            orphan: false,
+        }
+    }
+
     pub fn title(&self) -> String {
         let title = self.theme_config.get("title");
         if let Some(&Value::String(ref title)) = title {
@@ -72,4 +182,71 @@ impl Page {
 
         "Untitled".to_owned()
     }
+
+    /// Return the page's headings as a tree, for themes to render an in-page table of contents.
+    pub fn outline(&self) -> Vec<Value> {
+        nest_headings(&self.headings)
+    }
+
+    /// Return true unless the page sets the given theme-config value to false or to an
+    /// empty string. Pages use this to opt out of project-wide outputs such as the sitemap.
+    pub fn theme_config_enabled(&self, key: &str) -> bool {
+        match self.theme_config.get(key) {
+            Some(Value::Bool(enabled)) => *enabled,
+            Some(Value::String(value)) => !value.is_empty() && value != "false",
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heading(level: i8, id: &str) -> PageHeading {
+        PageHeading {
+            level,
+            id: id.to_owned(),
+            title: id.to_uppercase(),
+        }
+    }
+
+    #[test]
+    fn test_outline() {
+        let mut page = Page::new_for_test("index", "");
+        page.headings = vec![
+            heading(1, "a"),
+            heading(2, "b"),
+            heading(3, "c"),
+            heading(2, "d"),
+            heading(1, "e"),
+        ];
+
+        let outline = page.outline();
+        assert_eq!(outline.len(), 2);
+        assert_eq!(outline[0]["title"], json!("A"));
+        assert_eq!(outline[0]["url"], json!("#a"));
+        assert_eq!(outline[0]["children"][0]["id"], json!("b"));
+        assert_eq!(outline[0]["children"][0]["children"][0]["id"], json!("c"));
+        assert_eq!(outline[0]["children"][1]["id"], json!("d"));
+        assert_eq!(outline[1]["children"], json!([]));
+    }
+
+    #[test]
+    fn test_theme_config_enabled() {
+        let mut page = Page::new_for_test("index", "");
+
+        assert!(page.theme_config_enabled("search"));
+        for &(ref value, enabled) in &[
+            (json!(false), false),
+            (json!("false"), false),
+            (json!(""), false),
+            (json!(true), true),
+            (json!("true"), true),
+            (json!("no"), true),
+        ] {
+            page.theme_config.insert("search".to_owned(), value.clone());
+            assert_eq!(page.theme_config_enabled("search"), enabled);
+        }
+    }
 }
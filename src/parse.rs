@@ -459,6 +459,46 @@ mod tests {
         assert_eq!(parser.parse_string(0, ""), Ok(rocket(vec![], 0)));
     }
 
+    #[test]
+    fn test_colon() {
+        let mut parser = Parser::new();
+
+        // Colons outside of block delimiters are ordinary text, including within words
+        assert_eq!(
+            parser.parse_string(0, "Note: see (:ref server:install:) at 10:30."),
+            Ok(rocket(
+                vec![
+                    Node::new_string("Note: see ", 0, 0),
+                    Node::new_children(
+                        vec![
+                            Node::new_string("ref", 0, 0),
+                            Node::new_string("server:install", 0, 0),
+                        ],
+                        0,
+                        0,
+                    ),
+                    Node::new_string(" at 10:30.", 0, 0),
+                ],
+                0
+            ))
+        );
+
+        // A colon directly before the closing delimiter doesn't swallow it
+        assert_eq!(
+            parser.parse_string(0, "(:`` a:b:)"),
+            Ok(rocket(
+                vec![
+                    Node::new_children(
+                        vec![Node::new_string("``", 0, 0), Node::new_string("a:b", 0, 0)],
+                        0,
+                        0,
+                    ),
+                ],
+                0
+            ))
+        );
+    }
+
     #[test]
     fn test_word_with_quotes() {
         let mut parser = Parser::new();
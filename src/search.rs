@@ -0,0 +1,298 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use serde_json;
+use evaluator::Evaluator;
+use page::Page;
+use theme::strip_tags;
+
+/// A heading within a search document.
+#[derive(Debug, Serialize)]
+pub struct SearchHeading {
+    pub id: String,
+    pub title: String,
+}
+
+/// The searchable contents of a page.
+#[derive(Debug, Serialize)]
+pub struct SearchDocument {
+    pub slug: String,
+    pub title: String,
+
+    /// The page's URL relative to the project's root.
+    pub url: String,
+    pub headings: Vec<SearchHeading>,
+
+    /// The page's body with tags stripped and whitespace collapsed.
+    pub text: String,
+}
+
+impl SearchDocument {
+    /// Return the search document for a page given its linked body, or None if the page
+    /// opts out by setting the "search" theme-config value to false. Links in the page's
+    /// title and headings are resolved to plain text.
+    pub fn from_page(evaluator: &Evaluator, page: &Page, body: &str) -> Option<Self> {
+        if !page.theme_config_enabled("search") {
+            return None;
+        }
+
+        Some(SearchDocument {
+            slug: page.slug.as_ref().to_owned(),
+            title: evaluator.link_title(&page.title(), page),
+            url: page.slug.link_path(evaluator.urls.pretty_url),
+            headings: page.headings
+                .iter()
+                .map(|heading| SearchHeading {
+                    id: heading.id.to_owned(),
+                    title: evaluator.link_title(&heading.title, page),
+                })
+                .collect(),
+            text: collapse_whitespace(&strip_tags(body)),
+        })
+    }
+}
+
+/// The search.json file that themes load to search the project without a server.
+#[derive(Debug, Serialize)]
+pub struct SearchIndex {
+    pub documents: Vec<SearchDocument>,
+
+    /// Maps each stemmed term to the indices of the documents that contain it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub terms: Option<BTreeMap<String, Vec<usize>>>,
+}
+
+impl SearchIndex {
+    /// Create an index of the given documents, sorted by slug. If with_terms is true, the
+    /// index also includes an inverted index of their stemmed words.
+    pub fn new(mut documents: Vec<SearchDocument>, with_terms: bool) -> Self {
+        documents.sort_by(|a, b| a.slug.cmp(&b.slug));
+
+        let terms = if with_terms {
+            let mut terms: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+            for (i, document) in documents.iter().enumerate() {
+                let mut words: BTreeSet<String> = tokenize(&document.title).collect();
+                words.extend(tokenize(&document.text));
+                for heading in &document.headings {
+                    words.extend(tokenize(&heading.title));
+                }
+
+                for word in words {
+                    terms.entry(word).or_default().push(i);
+                }
+            }
+
+            Some(terms)
+        } else {
+            None
+        };
+
+        SearchIndex { documents, terms }
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let data = serde_json::to_string(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(data.as_bytes())
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Split text into lowercase, stemmed words. Single characters are skipped.
+pub fn tokenize<'a>(text: &'a str) -> impl Iterator<Item = String> + 'a {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 1)
+        .map(|word| stem(&word.to_lowercase()))
+}
+
+fn is_vowel(c: char) -> bool {
+    "aeiouy".contains(c)
+}
+
+/// Stems are never shortened below this many characters.
+const MIN_STEM_LEN: usize = 3;
+
+/// Reduce an English word to a stem by removing common inflectional suffixes, so that
+/// "configuring", "configured", and "configures" are all found by a search for "configure".
+/// It is deliberately much simpler than a Porter stemmer, since themes must stem search
+/// queries the same way.
+pub fn stem(word: &str) -> String {
+    let mut word = word.to_owned();
+    if word.len() <= MIN_STEM_LEN || !word.chars().all(|c| c.is_ascii_alphabetic()) {
+        return word;
+    }
+
+    if word.ends_with("ies") {
+        word.truncate(word.len() - 3);
+        word.push('y');
+    } else if word.ends_with("sses") {
+        word.truncate(word.len() - 2);
+    } else if word.ends_with('s') && !word.ends_with("ss") && !word.ends_with("us") {
+        word.pop();
+    }
+
+    for suffix in &["ing", "ed"] {
+        if !word.ends_with(suffix) {
+            continue;
+        }
+
+        let stem = word[..word.len() - suffix.len()].to_owned();
+        if !stem.chars().any(is_vowel) {
+            break;
+        }
+
+        let last = stem.chars().last().unwrap();
+        if stem.len() < MIN_STEM_LEN {
+            // Restore the "e" of short words such as "used", unless the stem ends in a
+            // vowel as in "seed"
+            if !is_vowel(last) {
+                word = stem + "e";
+            }
+            break;
+        }
+
+        // Undo the doubled consonant in words such as "running"
+        let previous = stem.chars().rev().nth(1).unwrap();
+        let stem_len = if last == previous && !is_vowel(last) && !"lsz".contains(last) {
+            stem.len() - 1
+        } else {
+            stem.len()
+        };
+
+        word.truncate(stem_len);
+        break;
+    }
+
+    // Drop a trailing "e" so that "configure" and "configuring" share a stem
+    if word.len() > MIN_STEM_LEN && word.ends_with('e') {
+        word.pop();
+    }
+
+    word
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use evaluator::{PlaceholderAction, RefDef, Worker};
+    use page::{PageHeading, Slug, UrlConfig, UrlStyle};
+    use serde_json::Value;
+
+    #[test]
+    fn test_stem() {
+        assert_eq!(stem("configure"), "configur");
+        assert_eq!(stem("configured"), "configur");
+        assert_eq!(stem("configuring"), "configur");
+        assert_eq!(stem("configures"), "configur");
+        assert_eq!(stem("running"), "run");
+        assert_eq!(stem("installed"), "install");
+        assert_eq!(stem("libraries"), "library");
+        assert_eq!(stem("classes"), "class");
+        assert_eq!(stem("status"), "status");
+        assert_eq!(stem("red"), "red");
+        assert_eq!(stem("seed"), "seed");
+        assert_eq!(stem("use"), "use");
+        assert_eq!(stem("used"), "use");
+        assert_eq!(stem("uses"), "use");
+        assert_eq!(stem("using"), "use");
+        assert_eq!(stem("x86"), "x86");
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let words: Vec<_> = tokenize("Installing Rocket: a how-to").collect();
+        assert_eq!(words, vec!["install", "rocket", "how", "to"]);
+    }
+
+    fn page(slug: &str, title: &str, body: &str) -> Page {
+        let mut page = Page::new_for_test(slug, body);
+        page.theme_config
+            .insert("title".to_owned(), Value::String(title.to_owned()));
+        page.headings = vec![PageHeading {
+            level: 1,
+            id: format!("ref-{}", slug),
+            title: title.to_owned(),
+        }];
+        page
+    }
+
+    #[test]
+    fn test_search_index() {
+        let install = page("tutorials/install", "Installing <em>Rocket</em>", "");
+        let body = "<h1>Installing Rocket</h1>\n<p>Run the\n   installer.</p>";
+        let evaluator = Evaluator::new();
+        let install = SearchDocument::from_page(&evaluator, &install, body).unwrap();
+        assert_eq!(install.title, "Installing Rocket");
+        assert_eq!(install.url, "tutorials/install");
+        assert_eq!(install.headings[0].title, "Installing Rocket");
+        assert_eq!(install.text, "Installing Rocket Run the installer.");
+
+        let mut hidden = page("hidden", "Hidden", "");
+        hidden
+            .theme_config
+            .insert("search".to_owned(), Value::String("false".to_owned()));
+        assert!(SearchDocument::from_page(&evaluator, &hidden, "").is_none());
+
+        let urls = UrlConfig::new(UrlStyle::Flat, None);
+        let flat = Evaluator::new_with_options(PathBuf::new(), urls);
+        let index = page("index", "Rocket", "");
+        let index = SearchDocument::from_page(&flat, &index, "Run it").unwrap();
+        assert_eq!(index.url, "index.html");
+
+        let search_index = SearchIndex::new(vec![install, index], true);
+        assert_eq!(search_index.documents[0].slug, "index");
+
+        let terms = search_index.terms.as_ref().unwrap();
+        assert_eq!(terms["rocket"], vec![0, 1]);
+        assert_eq!(terms["run"], vec![0, 1]);
+        assert_eq!(terms["install"], vec![1]);
+        assert_eq!(terms["installer"], vec![1]);
+        assert_eq!(terms.get("it"), Some(&vec![0]));
+        assert_eq!(terms.get("a"), None);
+        assert!(
+            !serde_json::to_string(&SearchIndex::new(vec![], false))
+                .unwrap()
+                .contains("terms")
+        );
+    }
+
+    #[test]
+    fn test_search_links() {
+        let evaluator = Evaluator::new();
+        let install = Slug::new("tutorials/install".to_owned());
+        let reference = Slug::new("reference".to_owned());
+        evaluator.titles.write().unwrap().insert(install, "Installation".to_owned());
+        evaluator.refdefs.write().unwrap().insert(
+            "ref-api".to_owned(),
+            RefDef::new("The <code>API</code>", &reference),
+        );
+
+        let mut worker = Worker::new(&evaluator);
+        worker.set_slug(Slug::new("guide".to_owned()));
+        let mut link = |target: &str, path, title| {
+            format!(
+                r#"<a href="{}">{}</a>"#,
+                worker.get_placeholder(target.to_owned(), path),
+                worker.get_placeholder(target.to_owned(), title)
+            )
+        };
+        let doc = link(
+            "tutorials/install",
+            PlaceholderAction::PagePath,
+            PlaceholderAction::PageTitle,
+        );
+        let api = link("ref-api", PlaceholderAction::Path, PlaceholderAction::Title);
+
+        let mut guide = page("guide", &format!("After {}", doc), "");
+        guide.headings[0].title = format!("Using {}", api);
+        let document = SearchDocument::from_page(&evaluator, &guide, "").unwrap();
+        assert_eq!(document.title, "After Installation");
+        assert_eq!(document.headings[0].title, "Using The API");
+        assert_eq!(evaluator.get_num_errors(), 0);
+    }
+}
@@ -0,0 +1,97 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use time;
+use directives::escape_string;
+
+/// A page as listed in sitemap.xml.
+#[derive(Debug)]
+pub struct SitemapEntry {
+    pub url: String,
+
+    /// The date the page was last modified, formatted as YYYY-MM-DD.
+    pub lastmod: Option<String>,
+}
+
+/// The pages that search engines should crawl, written as a sitemap.xml file.
+#[derive(Debug, Default)]
+pub struct Sitemap {
+    pub entries: Vec<SitemapEntry>,
+}
+
+impl Sitemap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, url: String, modified: Option<SystemTime>) {
+        self.entries.push(SitemapEntry {
+            url,
+            lastmod: modified.and_then(format_date),
+        });
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut result = vec![
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_owned(),
+            r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#.to_owned(),
+        ];
+
+        for entry in &self.entries {
+            let lastmod = match entry.lastmod {
+                Some(ref date) => format!("<lastmod>{}</lastmod>", date),
+                None => "".to_owned(),
+            };
+
+            result.push(format!(
+                "<url><loc>{}</loc>{}</url>",
+                escape_string(&entry.url),
+                lastmod
+            ));
+        }
+
+        result.push("</urlset>\n".to_owned());
+        result.join("\n")
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(self.to_xml().as_bytes())
+    }
+}
+
+/// Format a file modification time as a W3C date, which is what sitemaps expect.
+fn format_date(modified: SystemTime) -> Option<String> {
+    let seconds = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let tm = time::at_utc(time::Timespec::new(seconds as i64, 0));
+    tm.strftime("%Y-%m-%d").ok().map(|date| date.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_to_xml() {
+        let mut sitemap = Sitemap::new();
+        sitemap.add("https://example.com/docs/".to_owned(), None);
+        sitemap.add(
+            "https://example.com/docs/a&b".to_owned(),
+            Some(UNIX_EPOCH + Duration::from_secs(1_500_000_000)),
+        );
+
+        assert_eq!(
+            sitemap.to_xml(),
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+                "<url><loc>https://example.com/docs/</loc></url>\n",
+                "<url><loc>https://example.com/docs/a&amp;b</loc>",
+                "<lastmod>2017-07-14</lastmod></url>\n",
+                "</urlset>\n"
+            )
+        );
+    }
+}
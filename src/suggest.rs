@@ -0,0 +1,62 @@
+use std::cmp;
+
+/// Return the Levenshtein distance between two strings, counted in characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..b.len() + 1).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == cb { 0 } else { 1 };
+            current[j + 1] = cmp::min(substitution, cmp::min(previous[j + 1], current[j]) + 1);
+        }
+
+        previous.clone_from(&current);
+    }
+
+    previous[b.len()]
+}
+
+/// Return the candidate closest to the given name, if any is close enough to plausibly be
+/// what was meant. Ties are broken alphabetically so that the suggestion is stable.
+pub fn suggest<'a, I>(name: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let max_distance = cmp::max(1, name.chars().count() / 3);
+
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("toctree", "toctre"), 1);
+        assert_eq!(edit_distance("crème", "creme"), 1);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let candidates = ["define", "define-ref", "define-template", "ref", "h1", "h2"];
+        assert_eq!(suggest("defin", candidates.iter().cloned()), Some("define"));
+        assert_eq!(suggest("defne-ref", candidates.iter().cloned()), Some("define-ref"));
+        assert_eq!(suggest("h3", candidates.iter().cloned()), Some("h1"));
+        assert_eq!(suggest("glossary", candidates.iter().cloned()), None);
+        assert_eq!(suggest("ref", candidates.iter().cloned()), None);
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashSet;
+
+/// A boolean expression over build tags, such as "enterprise and not cloud". "not" binds
+/// tighter than "and", which binds tighter than "or".
+#[derive(Debug, PartialEq)]
+pub enum TagExpression {
+    Tag(String),
+    Not(Box<TagExpression>),
+    And(Vec<TagExpression>),
+    Or(Vec<TagExpression>),
+}
+
+struct TagParser<'a> {
+    tokens: Vec<&'a str>,
+    position: usize,
+}
+
+impl<'a> TagParser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).cloned()
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<TagExpression, String> {
+        let mut terms = vec![self.parse_and()?];
+        while self.peek() == Some("or") {
+            self.next();
+            terms.push(self.parse_and()?);
+        }
+
+        Ok(if terms.len() == 1 {
+            terms.pop().unwrap()
+        } else {
+            TagExpression::Or(terms)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<TagExpression, String> {
+        let mut factors = vec![self.parse_not()?];
+        while self.peek() == Some("and") {
+            self.next();
+            factors.push(self.parse_not()?);
+        }
+
+        Ok(if factors.len() == 1 {
+            factors.pop().unwrap()
+        } else {
+            TagExpression::And(factors)
+        })
+    }
+
+    fn parse_not(&mut self) -> Result<TagExpression, String> {
+        match self.next() {
+            Some("not") => Ok(TagExpression::Not(Box::new(self.parse_not()?))),
+            Some("and") | Some("or") => Err("Expected a tag before operator".to_owned()),
+            Some(tag) => {
+                let valid = tag.chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+                if !valid {
+                    return Err(format!("Invalid tag name: '{}'", tag));
+                }
+
+                Ok(TagExpression::Tag(tag.to_owned()))
+            }
+            None => Err("Unexpected end of tag expression".to_owned()),
+        }
+    }
+}
+
+impl TagExpression {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = TagParser {
+            tokens: text.split_whitespace().collect(),
+            position: 0,
+        };
+
+        let expression = parser.parse_or()?;
+        match parser.peek() {
+            Some(token) => Err(format!("Unexpected '{}' in tag expression", token)),
+            None => Ok(expression),
+        }
+    }
+
+    pub fn matches(&self, tags: &HashSet<String>) -> bool {
+        match *self {
+            TagExpression::Tag(ref tag) => tags.contains(tag),
+            TagExpression::Not(ref expression) => !expression.matches(tags),
+            TagExpression::And(ref expressions) => expressions.iter().all(|e| e.matches(tags)),
+            TagExpression::Or(ref expressions) => expressions.iter().any(|e| e.matches(tags)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|s| (*s).to_owned()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(TagExpression::parse("").is_err());
+        assert!(TagExpression::parse("and").is_err());
+        assert!(TagExpression::parse("a and").is_err());
+        assert!(TagExpression::parse("a b").is_err());
+        assert!(TagExpression::parse("a.b").is_err());
+
+        assert_eq!(
+            TagExpression::parse("a or not b and c"),
+            Ok(TagExpression::Or(vec![
+                TagExpression::Tag("a".to_owned()),
+                TagExpression::And(vec![
+                    TagExpression::Not(Box::new(TagExpression::Tag("b".to_owned()))),
+                    TagExpression::Tag("c".to_owned()),
+                ]),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_matches() {
+        let expression = TagExpression::parse("enterprise and not cloud").unwrap();
+        assert!(expression.matches(&tags(&["enterprise"])));
+        assert!(!expression.matches(&tags(&["enterprise", "cloud"])));
+        assert!(!expression.matches(&tags(&[])));
+
+        let expression = TagExpression::parse("community or cloud").unwrap();
+        assert!(expression.matches(&tags(&["cloud"])));
+        assert!(!expression.matches(&tags(&["enterprise"])));
+    }
+}
@@ -1,10 +1,12 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use page::{Page, Slug};
-use toctree::TocTree;
+use directives::escape_string;
+use page::{Page, Slug, UrlConfig};
+use toctree::{TocTree, MAIN_TOCTREE};
 use handlebars::{self, Handlebars};
 use regex::Regex;
 use serde_json;
@@ -14,8 +16,37 @@ lazy_static! {
     static ref PAT_TAGS: Regex = Regex::new("<[^>]+>").expect("Failed to compile striptags regex");
 }
 
+pub fn strip_tags(html: &str) -> Cow<'_, str> {
+    PAT_TAGS.replace_all(html, "")
+}
+
+/// Return a minimal page that immediately sends the reader to another URL.
+pub fn render_redirect(url: &str) -> String {
+    let url = escape_string(url);
+    format!(
+        concat!(
+            "<!DOCTYPE html>\n",
+            "<html><head><meta charset=\"utf-8\"><title>Redirecting</title>",
+            "<link rel=\"canonical\" href=\"{0}\">",
+            "<meta http-equiv=\"refresh\" content=\"0; url={0}\"></head>",
+            "<body><p>This page has moved to <a href=\"{0}\">{0}</a>.</p></body></html>\n"
+        ),
+        url
+    )
+}
+
+fn get_current_slug(rc: &handlebars::RenderContext) -> Result<Slug, handlebars::RenderError> {
+    match rc.context().data().get("current_slug") {
+        Some(&serde_json::value::Value::String(ref s)) => Ok(Slug::new(s.to_owned())),
+        _ => Err(handlebars::RenderError::new(
+            "Unable to get current slug while rendering template",
+        )),
+    }
+}
+
 struct TocTreeHelper {
     toctree: Arc<TocTree>,
+    named_toctrees: Arc<HashMap<String, TocTree>>,
 }
 
 impl handlebars::HelperDef for TocTreeHelper {
@@ -25,18 +56,27 @@ impl handlebars::HelperDef for TocTreeHelper {
         _: &Handlebars,
         rc: &mut handlebars::RenderContext,
     ) -> Result<(), handlebars::RenderError> {
-        let slug = h.param(0).unwrap().value().as_str().unwrap();
+        // {{toctree tree="name"}} renders a named tree, starting from its root unless a page
+        // is given
+        let toctree = match h.hash_get("tree").and_then(|tree| tree.value().as_str()) {
+            None | Some(MAIN_TOCTREE) => self.toctree.as_ref(),
+            Some(name) => self.named_toctrees.get(name).ok_or_else(|| {
+                handlebars::RenderError::new(format!("Unknown toctree '{}'", name))
+            })?,
+        };
 
-        let current_path = match rc.context().data().get("current_slug") {
-            Some(&serde_json::value::Value::String(ref s)) => Ok(s.to_owned()),
-            _ => Err(handlebars::RenderError::new(
-                "Unable to get current slug while rendering template",
-            )),
-        }?;
-        let current_slug = Slug::new(current_path);
+        let root = match h.param(0).and_then(|param| param.value().as_str()) {
+            Some(slug) => Slug::new(slug.to_owned()),
+            None => toctree.root().to_owned(),
+        };
 
-        let html = self.toctree
-            .generate_html(&Slug::new(slug.to_owned()), &current_slug, true)
+        let current_slug = get_current_slug(rc)?;
+        let maxdepth = h.hash_get("maxdepth")
+            .and_then(|depth| depth.value().as_u64())
+            .map(|depth| depth as usize);
+
+        let html = toctree
+            .generate_html(&root, &current_slug, true, maxdepth)
             .or_else(|msg| Err(handlebars::RenderError::new(msg)))?
             .concat();
         rc.writer.write_all(html.as_bytes())?;
@@ -44,6 +84,101 @@ impl handlebars::HelperDef for TocTreeHelper {
     }
 }
 
+struct BreadcrumbsHelper {
+    toctree: Arc<TocTree>,
+}
+
+impl handlebars::HelperDef for BreadcrumbsHelper {
+    fn call(
+        &self,
+        h: &handlebars::Helper,
+        _: &Handlebars,
+        rc: &mut handlebars::RenderContext,
+    ) -> Result<(), handlebars::RenderError> {
+        let slug = h.param(0)
+            .and_then(|param| param.value().as_str())
+            .ok_or_else(|| handlebars::RenderError::new("breadcrumbs requires a page slug"))?;
+        let current_slug = get_current_slug(rc)?;
+        let html = self.toctree
+            .generate_breadcrumbs_html(&Slug::new(slug.to_owned()), &current_slug);
+        rc.writer.write_all(html.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Render the list items for a page outline, skipping headings above min_level but keeping
+/// their descendants.
+fn outline_items(
+    entries: &[serde_json::Value],
+    min_level: i64,
+    maxdepth: Option<usize>,
+) -> Vec<String> {
+    let mut items = vec![];
+    if maxdepth == Some(0) {
+        return items;
+    }
+
+    for entry in entries {
+        let children = entry["children"]
+            .as_array()
+            .map(|children| children.as_slice())
+            .unwrap_or(&[]);
+
+        if entry["level"].as_i64().unwrap_or(0) < min_level {
+            items.extend(outline_items(children, min_level, maxdepth));
+            continue;
+        }
+
+        let nested = outline_items(children, min_level, maxdepth.map(|depth| depth - 1));
+        let nested = if nested.is_empty() {
+            String::new()
+        } else {
+            format!("<ul>{}</ul>", nested.concat())
+        };
+
+        items.push(format!(
+            r#"<li><a href="{}">{}</a>{}</li>"#,
+            escape_string(entry["url"].as_str().unwrap_or("")),
+            strip_tags(entry["title"].as_str().unwrap_or("")),
+            nested
+        ));
+    }
+
+    items
+}
+
+/// Renders the current page's outline. The optional parameter is the level of the highest
+/// heading to include, so that 2 omits the page title.
+struct PageTocHelper;
+
+impl handlebars::HelperDef for PageTocHelper {
+    fn call(
+        &self,
+        h: &handlebars::Helper,
+        _: &Handlebars,
+        rc: &mut handlebars::RenderContext,
+    ) -> Result<(), handlebars::RenderError> {
+        let min_level = h.param(0).and_then(|level| level.value().as_i64()).unwrap_or(1);
+        let maxdepth = h.hash_get("maxdepth")
+            .and_then(|depth| depth.value().as_u64())
+            .map(|depth| depth as usize);
+
+        let items = match rc.context().data().get("outline") {
+            Some(serde_json::Value::Array(outline)) => {
+                outline_items(outline, min_level, maxdepth)
+            }
+            _ => vec![],
+        };
+
+        if !items.is_empty() {
+            let html = format!(r#"<ul class="page-toc">{}</ul>"#, items.concat());
+            rc.writer.write_all(html.as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
 struct StripTags;
 
 impl handlebars::HelperDef for StripTags {
@@ -54,7 +189,7 @@ impl handlebars::HelperDef for StripTags {
         rc: &mut handlebars::RenderContext,
     ) -> Result<(), handlebars::RenderError> {
         let arg = h.param(0).unwrap().value().as_str().unwrap();
-        let stripped = PAT_TAGS.replace_all(arg, "");
+        let stripped = strip_tags(arg);
         rc.writer.write_all(stripped.as_bytes())?;
         Ok(())
     }
@@ -91,12 +226,18 @@ impl Theme {
 pub struct Renderer {
     handlebars: Handlebars,
     constants: serde_json::map::Map<String, serde_json::Value>,
+    toctree: Arc<TocTree>,
+    backlinks: HashMap<Slug, Vec<(Slug, String)>>,
+    urls: UrlConfig,
 }
 
 impl Renderer {
     pub fn new(
         theme: Theme,
         toctree: &Arc<TocTree>,
+        named_toctrees: &Arc<HashMap<String, TocTree>>,
+        backlinks: HashMap<Slug, Vec<(Slug, String)>>,
+        urls: UrlConfig,
     ) -> Result<Renderer, handlebars::TemplateFileError> {
         let mut handlebars = Handlebars::new();
         let theme_dir_path = theme.path.parent().unwrap_or_else(|| Path::new(""));
@@ -108,14 +249,25 @@ impl Renderer {
 
         let helper = TocTreeHelper {
             toctree: Arc::clone(toctree),
+            named_toctrees: Arc::clone(named_toctrees),
         };
 
         handlebars.register_helper("striptags", Box::new(StripTags));
         handlebars.register_helper("toctree", Box::new(helper));
+        handlebars.register_helper("page_toc", Box::new(PageTocHelper));
+        handlebars.register_helper(
+            "breadcrumbs",
+            Box::new(BreadcrumbsHelper {
+                toctree: Arc::clone(toctree),
+            }),
+        );
 
         Ok(Renderer {
             handlebars,
             constants: theme.constants,
+            toctree: Arc::clone(toctree),
+            backlinks,
+            urls,
         })
     }
 
@@ -126,14 +278,135 @@ impl Renderer {
         page: &Page,
         body: &str,
     ) -> Result<String, handlebars::RenderError> {
+        let backlinks: Vec<_> = self.backlinks
+            .get(&page.slug)
+            .map(|pages| pages.as_slice())
+            .unwrap_or(&[])
+            .iter()
+            .map(|(slug, title)| {
+                json!({
+                    "slug": slug.as_ref(),
+                    "title": title,
+                    "url": self.urls.page_url_from(&page.slug, slug),
+                })
+            })
+            .collect();
+
+        let page_link = |slug: Option<&Slug>| match slug {
+            Some(slug) => json!({
+                "slug": slug.as_ref(),
+                "title": self.toctree.get_title(slug),
+                "url": self.urls.page_url_from(&page.slug, slug),
+            }),
+            None => serde_json::Value::Null,
+        };
+
         let ctx = json!({
             "current_slug": serde_json::value::Value::String(page.slug.as_ref().to_owned()),
             "page": &page.theme_config,
+            "outline": page.outline(),
             "project": project_args,
             "theme": self.constants,
             "body": body,
+            "backlinks": backlinks,
+            "prev": page_link(self.toctree.previous(&page.slug)),
+            "next": page_link(self.toctree.next(&page.slug)),
+            "breadcrumbs": self.toctree
+                .breadcrumbs(&page.slug)
+                .into_iter()
+                .map(|slug| page_link(Some(slug)))
+                .collect::<Vec<_>>(),
         });
 
         self.handlebars.render(template_name, &ctx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use page::PageHeading;
+
+    fn outline() -> Vec<serde_json::Value> {
+        // The h3 directly below the h1 skips a level
+        let headings = [(1, "a"), (3, "b"), (2, "c"), (3, "d"), (1, "e"), (2, "f")];
+        let mut page = Page::new_for_test("index", "");
+        page.headings = headings
+            .iter()
+            .map(|&(level, id)| PageHeading {
+                level,
+                id: id.to_owned(),
+                title: id.to_uppercase(),
+            })
+            .collect();
+
+        page.outline()
+    }
+
+    fn item(id: &str, nested: &str) -> String {
+        format!(r##"<li><a href="#{}">{}</a>{}</li>"##, id, id.to_uppercase(), nested)
+    }
+
+    #[test]
+    fn test_outline_items() {
+        let outline = outline();
+        let d = item("d", "");
+        let c = item("c", &format!("<ul>{}</ul>", d));
+        let f = item("f", "");
+
+        assert_eq!(
+            outline_items(&outline, 1, None),
+            vec![
+                item("a", &format!("<ul>{}{}</ul>", item("b", ""), c)),
+                item("e", &format!("<ul>{}</ul>", f)),
+            ]
+        );
+        assert_eq!(outline_items(&outline, 1, Some(1)), vec![item("a", ""), item("e", "")]);
+
+        // Headings above min_level are dropped, but their descendants are promoted
+        assert_eq!(
+            outline_items(&outline, 2, None),
+            vec![item("b", ""), c.clone(), f.clone()]
+        );
+        assert_eq!(
+            outline_items(&outline, 2, Some(1)),
+            vec![item("b", ""), item("c", ""), f.clone()]
+        );
+        assert_eq!(outline_items(&outline, 3, None), vec![item("b", ""), d.clone()]);
+        assert!(outline_items(&outline, 1, Some(0)).is_empty());
+        assert!(outline_items(&outline, 4, None).is_empty());
+    }
+
+    #[test]
+    fn test_outline_items_strip_tags() {
+        let outline = json!([{
+            "level": 1,
+            "title": r#"Using <a href="../api"><code>run</code></a>"#,
+            "url": "#using-run",
+            "children": [],
+        }]);
+        let outline = outline.as_array().unwrap();
+        assert_eq!(
+            outline_items(outline, 1, None),
+            vec![r##"<li><a href="#using-run">Using run</a></li>"##]
+        );
+    }
+
+    #[test]
+    fn test_page_toc_helper() {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper("page_toc", Box::new(PageTocHelper));
+        let data = json!({ "outline": outline() });
+
+        assert_eq!(
+            handlebars.render_template("{{page_toc 2 maxdepth=1}}", &data).unwrap(),
+            format!(
+                r#"<ul class="page-toc">{}{}{}</ul>"#,
+                item("b", ""),
+                item("c", ""),
+                item("f", "")
+            )
+        );
+        assert_eq!(handlebars.render_template("{{page_toc maxdepth=0}}", &data).unwrap(), "");
+    }
+}
@@ -1,11 +1,77 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
-use page::Slug;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use glob;
+use directives::escape_string;
+use evaluator::SourceLocation;
+use page::{Slug, UrlConfig};
+
+/// The name of the tree that drives reading order, breadcrumbs, and section numbering.
+pub const MAIN_TOCTREE: &str = "main";
+
+/// What a toctree entry points to.
+#[derive(Debug)]
+pub enum TocTreeTarget {
+    Page(Slug),
+
+    /// Every page whose slug matches the pattern, in alphabetical order. Expanded by finish().
+    Glob(glob::Pattern),
+
+    External(String),
+}
+
+/// Settings given to a toctree directive, which apply to each of its entries.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TocTreeOptions {
+    /// The number of levels to render for each entry, counting the entry itself.
+    pub maxdepth: Option<usize>,
+
+    /// Hidden entries are part of the page hierarchy, but aren't rendered.
+    pub hidden: bool,
+}
 
 #[derive(Debug)]
-struct TocTreeElement {
-    slug: Slug,
+pub struct TocTreeElement {
+    target: TocTreeTarget,
     title: Option<String>,
+    options: TocTreeOptions,
+
+    /// Where the entry was listed, for reporting missing pages.
+    location: Option<SourceLocation>,
+}
+
+impl TocTreeElement {
+    pub fn new(target: TocTreeTarget, title: Option<String>, options: TocTreeOptions) -> Self {
+        TocTreeElement {
+            target,
+            title,
+            options,
+            location: None,
+        }
+    }
+
+    pub fn with_location(self, location: SourceLocation) -> Self {
+        TocTreeElement {
+            location: Some(location),
+            ..self
+        }
+    }
+
+    fn slug(&self) -> Option<&Slug> {
+        match self.target {
+            TocTreeTarget::Page(ref slug) => Some(slug),
+            _ => None,
+        }
+    }
+}
+
+/// Return the smaller of two optional depth limits, where None means unlimited.
+fn min_depth(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
 }
 
 pub struct TocTree {
@@ -18,75 +84,419 @@ pub struct TocTree {
     inverse_children: HashMap<Slug, Vec<Slug>>,
 
     titles: HashMap<Slug, String>,
-    pretty_url: bool,
+    urls: UrlConfig,
+
+    /// Every page in the tree, in the order of a depth-first walk from the root.
+    reading_order: Vec<Slug>,
+    reading_positions: HashMap<Slug, usize>,
+
+    /// Maps child -> the parent it is shown under in breadcrumbs
+    primary_parents: HashMap<Slug, Slug>,
+
+    /// If true, entries are prefixed with their section numbers.
+    numbered: bool,
+
+    /// Maps page -> its position under its primary parent, such as "3.2"
+    section_numbers: HashMap<Slug, String>,
 }
 
 impl TocTree {
-    pub fn new(root: Slug, pretty_url: bool) -> Self {
+    pub fn new(root: Slug, urls: UrlConfig) -> Self {
         TocTree {
             root: root,
             children: HashMap::new(),
             inverse_children: HashMap::new(),
             titles: HashMap::new(),
-            pretty_url: pretty_url,
+            urls: urls,
+            reading_order: vec![],
+            reading_positions: HashMap::new(),
+            primary_parents: HashMap::new(),
+            numbered: false,
+            section_numbers: HashMap::new(),
         }
     }
 
     pub fn new_empty() -> Self {
-        Self::new(Slug::new("".to_owned()), false)
+        Self::new(Slug::new("".to_owned()), UrlConfig::default())
     }
 
-    pub fn add(&mut self, parent_slug: &Slug, child: Slug, title: Option<String>) {
-        let new_element = TocTreeElement {
-            slug: child.to_owned(),
-            title: title,
-        };
+    pub fn root(&self) -> &Slug {
+        &self.root
+    }
+
+    /// Return an error message if the tree's root page doesn't exist. Only meaningful once
+    /// the tree is finished.
+    pub fn check_root(&self, name: &str) -> Option<String> {
+        if self.titles.contains_key(&self.root) {
+            return None;
+        }
+
+        Some(format!("Unknown root page '{}' for toctree '{}'", self.root, name))
+    }
+
+    pub fn set_root(&mut self, root: Slug) {
+        self.root = root;
+    }
+
+    pub fn set_numbered(&mut self, numbered: bool) {
+        self.numbered = numbered;
+    }
+
+    pub fn add(&mut self, parent_slug: &Slug, new_element: TocTreeElement) {
+        if let TocTreeTarget::Page(ref child) = new_element.target {
+            self.inverse_children
+                .entry(child.to_owned())
+                .or_insert_with(|| vec![])
+                .push(parent_slug.to_owned());
+        }
 
-        self.inverse_children
-            .entry(child)
-            .or_insert_with(|| vec![])
-            .push(parent_slug.to_owned());
         self.children
             .entry(parent_slug.to_owned())
             .or_insert_with(|| vec![])
             .push(new_element);
     }
 
-    pub fn finish(&mut self, titles: &HashMap<Slug, String>) {
+    /// Prepare the tree for rendering once every page has been compiled. Returns an error
+    /// message for each entry that names a page that doesn't exist; such entries are dropped.
+    pub fn finish(&mut self, titles: &HashMap<Slug, String>) -> Vec<String> {
         self.titles = titles.clone();
+        let mut errors = self.remove_missing_pages();
+        self.expand_globs();
+        errors.extend(self.break_cycles());
+
+        let mut reading_order = vec![];
+        let root = self.root.to_owned();
+        self.walk(&root, &mut reading_order, &mut HashSet::new());
+        self.reading_positions = reading_order
+            .iter()
+            .enumerate()
+            .map(|(i, slug)| (slug.to_owned(), i))
+            .collect();
+        self.reading_order = reading_order;
+
+        // A page listed by several parents belongs to the one that is read first
+        let mut primary_parents = HashMap::new();
+        for slug in &self.reading_order {
+            let parent = self.inverse_children
+                .get(slug)
+                .into_iter()
+                .flat_map(|parents| parents.iter())
+                .filter_map(|parent| Some((*self.reading_positions.get(parent)?, parent)))
+                .min_by_key(|&(position, _)| position);
+
+            if let Some((_, parent)) = parent {
+                primary_parents.insert(slug.to_owned(), parent.to_owned());
+            }
+        }
+
+        self.primary_parents = primary_parents;
+
+        // Number each page by its position under its primary parent. The root is unnumbered
+        let mut counters: HashMap<&Slug, usize> = HashMap::new();
+        let mut section_numbers: HashMap<Slug, String> = HashMap::new();
+        for slug in &self.reading_order {
+            let parent = match self.primary_parents.get(slug) {
+                Some(parent) => parent,
+                None => continue,
+            };
+
+            let counter = counters.entry(parent).or_insert(0);
+            *counter += 1;
+            let number = match section_numbers.get(parent) {
+                Some(prefix) => format!("{}.{}", prefix, counter),
+                None => counter.to_string(),
+            };
+
+            section_numbers.insert(slug.to_owned(), number);
+        }
+
+        self.section_numbers = section_numbers;
+        errors
+    }
+
+    /// Return the section number of every page in the tree other than the root.
+    pub fn section_numbers(&self) -> &HashMap<Slug, String> {
+        &self.section_numbers
+    }
+
+    fn remove_missing_pages(&mut self) -> Vec<String> {
+        let mut errors = vec![];
+        let titles = &self.titles;
+        for children in self.children.values_mut() {
+            children.retain(|child| match child.target {
+                TocTreeTarget::Page(ref slug) if !titles.contains_key(slug) => {
+                    let location = match child.location {
+                        Some(ref location) => location.to_string(),
+                        None => "?".to_owned(),
+                    };
+
+                    errors.push(format!("Unknown page '{}' in toctree\n  --> {}", slug, location));
+                    false
+                }
+                _ => true,
+            });
+        }
+
+        self.inverse_children.retain(|child, _| titles.contains_key(child));
+        errors.sort();
+        errors
+    }
+
+    /// Return true if the page can be reached from the root of the tree.
+    pub fn contains(&self, slug: &Slug) -> bool {
+        self.reading_positions.contains_key(slug)
+    }
+
+    /// Replace each glob entry with the pages it matches, skipping the parent itself and any
+    /// pages that its toctree already lists.
+    fn expand_globs(&mut self) {
+        let mut slugs: Vec<&Slug> = self.titles.keys().collect();
+        slugs.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+
+        let match_options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..glob::MatchOptions::new()
+        };
+
+        let mut new_edges = vec![];
+        for (parent, children) in &mut self.children {
+            let mut listed: HashSet<Slug> = children
+                .iter()
+                .filter_map(|child| child.slug().cloned())
+                .collect();
+
+            let mut expanded = Vec::with_capacity(children.len());
+            for child in mem::take(children) {
+                if let TocTreeTarget::Glob(ref pattern) = child.target {
+                    for slug in &slugs {
+                        if *slug == parent || listed.contains(*slug) {
+                            continue;
+                        }
+
+                        if pattern.matches_with(slug.as_ref(), &match_options) {
+                            listed.insert((*slug).to_owned());
+                            new_edges.push(((*slug).to_owned(), parent.to_owned()));
+                            let target = TocTreeTarget::Page((*slug).to_owned());
+                            expanded.push(TocTreeElement {
+                                location: child.location.clone(),
+                                ..TocTreeElement::new(target, None, child.options)
+                            });
+                        }
+                    }
+
+                    continue;
+                }
+
+                expanded.push(child);
+            }
+
+            *children = expanded;
+        }
+
+        for (child, parent) in new_edges {
+            self.inverse_children.entry(child).or_default().push(parent);
+        }
+    }
+
+    /// Remove every entry that leads back to one of its own ancestors, so that the tree can
+    /// be traversed without recursing forever. Returns an error message for each cycle.
+    fn break_cycles(&mut self) -> Vec<String> {
+        let mut starts: Vec<Slug> = self.children.keys().cloned().collect();
+        starts.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+        starts.insert(0, self.root.to_owned());
+
+        let mut visited = HashSet::new();
+        let mut back_edges = vec![];
+        for start in &starts {
+            self.find_back_edges(start, &mut vec![], &mut visited, &mut back_edges);
+        }
+
+        let mut errors = vec![];
+        for (parent, child, cycle) in back_edges {
+            let cycle: Vec<&str> = cycle.iter().map(|slug| slug.as_ref()).collect();
+            let location = self.children[&parent]
+                .iter()
+                .find(|element| element.slug() == Some(&child))
+                .and_then(|element| element.location.as_ref())
+                .map(|location| location.to_string())
+                .unwrap_or_else(|| "?".to_owned());
+
+            errors.push(format!(
+                "Cycle in toctree: {}\n  --> {}",
+                cycle.join(" -> "),
+                location
+            ));
+
+            if let Some(children) = self.children.get_mut(&parent) {
+                children.retain(|element| element.slug() != Some(&child));
+            }
+
+            if let Some(parents) = self.inverse_children.get_mut(&child) {
+                parents.retain(|slug| *slug != parent);
+            }
+        }
+
+        errors
+    }
+
+    /// Depth-first search for entries that point at a page on the current path. Each is
+    /// recorded as (parent, child, cycle).
+    fn find_back_edges(
+        &self,
+        slug: &Slug,
+        path: &mut Vec<Slug>,
+        visited: &mut HashSet<Slug>,
+        back_edges: &mut Vec<(Slug, Slug, Vec<Slug>)>,
+    ) {
+        if !visited.insert(slug.to_owned()) {
+            return;
+        }
+
+        path.push(slug.to_owned());
+        if let Some(children) = self.children.get(slug) {
+            for child in children.iter().filter_map(|child| child.slug()) {
+                match path.iter().position(|ancestor| ancestor == child) {
+                    Some(start) => {
+                        let seen = back_edges
+                            .iter()
+                            .any(|(p, c, _)| p == slug && c == child);
+                        if !seen {
+                            let mut cycle = path[start..].to_vec();
+                            cycle.push(child.to_owned());
+                            back_edges.push((slug.to_owned(), child.to_owned(), cycle));
+                        }
+                    }
+                    None => self.find_back_edges(child, path, visited, back_edges),
+                }
+            }
+        }
+
+        path.pop();
+    }
+
+    /// Append the pages below (and including) the given page in reading order. A page
+    /// listed in several toctrees is only visited the first time it is reached.
+    fn walk(&self, slug: &Slug, order: &mut Vec<Slug>, visited: &mut HashSet<Slug>) {
+        if !self.titles.contains_key(slug) || !visited.insert(slug.to_owned()) {
+            return;
+        }
+
+        order.push(slug.to_owned());
+        if let Some(children) = self.children.get(slug) {
+            for child in children.iter().filter_map(|child| child.slug()) {
+                self.walk(child, order, visited);
+            }
+        }
+    }
+
+    /// Return the page before the given one in reading order.
+    pub fn previous(&self, slug: &Slug) -> Option<&Slug> {
+        let position = *self.reading_positions.get(slug)?;
+        if position == 0 {
+            return None;
+        }
+
+        self.reading_order.get(position - 1)
+    }
+
+    /// Return the page after the given one in reading order.
+    pub fn next(&self, slug: &Slug) -> Option<&Slug> {
+        let position = *self.reading_positions.get(slug)?;
+        self.reading_order.get(position + 1)
+    }
+
+    /// Return the path from the root to the given page, inclusive. Pages outside of the
+    /// tree have no breadcrumbs.
+    pub fn breadcrumbs(&self, slug: &Slug) -> Vec<&Slug> {
+        if !self.reading_positions.contains_key(slug) {
+            return vec![];
+        }
+
+        let mut trail = vec![];
+        let mut current = &self.reading_order[self.reading_positions[slug]];
+        loop {
+            trail.push(current);
+            current = match self.primary_parents.get(current) {
+                Some(parent) if *current != self.root => parent,
+                _ => break,
+            };
+        }
+
+        trail.reverse();
+        trail
+    }
+
+    pub fn generate_breadcrumbs_html(&self, slug: &Slug, current_slug: &Slug) -> String {
+        let mut result = vec![r#"<ol class="breadcrumbs">"#.to_owned()];
+        for slug in self.breadcrumbs(slug) {
+            let title = self.get_title(slug).unwrap_or("");
+            if slug == current_slug {
+                result.push(format!("<li>{}</li>", title));
+            } else {
+                result.push(format!(
+                    r#"<li><a href="{}">{}</a></li>"#,
+                    self.urls.page_url_from(current_slug, slug),
+                    title
+                ));
+            }
+        }
+
+        result.push("</ol>".to_owned());
+        result.concat()
+    }
+
+    pub fn get_title(&self, slug: &Slug) -> Option<&str> {
+        self.titles.get(slug).map(|title| title.as_str())
     }
 
+    /// Render the toctree below the given page. If maxdepth is given, only that many levels
+    /// are rendered.
     pub fn generate_html(
         &self,
         root: &Slug,
         current_slug: &Slug,
         is_root: bool,
+        maxdepth: Option<usize>,
     ) -> Result<Vec<Cow<'static, str>>, String> {
-        let children = match self.children.get(root) {
-            Some(children) => children,
-            None => {
-                return Ok(vec![Cow::Borrowed("")]);
-            }
+        let children: Vec<_> = match self.children.get(root) {
+            Some(children) => children.iter().filter(|c| !c.options.hidden).collect(),
+            None => vec![],
         };
 
+        if children.is_empty() || maxdepth == Some(0) {
+            return Ok(vec![Cow::Borrowed("")]);
+        }
+
         let mut result = vec![];
         result.push(Cow::Borrowed("<ul>"));
 
-        if is_root {
+        // A missing root is reported by check_root(), so it is simply left out here
+        let root_title = if is_root { self.titles.get(&self.root) } else { None };
+        if let Some(title) = root_title {
             result.push(Cow::Borrowed(r#"<li class="current">"#));
-            let title = self.titles
-                .get(&self.root)
-                .ok_or_else(|| format!("Failed to find toctree root '{}'", &self.root))?;
             result.push(Cow::Owned(format!(
                 r#"<a href="{}">{}</a>"#,
-                current_slug.path_to("", self.pretty_url),
+                self.urls.page_url_from(current_slug, &self.root),
                 title
             )));
             result.push(Cow::Borrowed("</li>"));
         }
 
         for child in children {
-            if self.is_ancestor_of(&child.slug, current_slug) {
+            let slug = match child.target {
+                TocTreeTarget::Page(ref slug) => slug,
+                TocTreeTarget::External(ref url) => {
+                    result.push(Cow::Owned(format!(
+                        r#"<li class="external"><a href="{}">{}</a></li>"#,
+                        escape_string(url),
+                        child.title.as_ref().unwrap_or(url)
+                    )));
+                    continue;
+                }
+                TocTreeTarget::Glob(_) => continue,
+            };
+
+            if self.is_ancestor_of(slug, current_slug) {
                 result.push(Cow::Borrowed(r#"<li class="current">"#));
             } else {
                 result.push(Cow::Borrowed("<li>"));
@@ -95,16 +505,30 @@ impl TocTree {
             let title = match child.title.as_ref() {
                 Some(t) => t,
                 None => self.titles
-                    .get(&child.slug)
-                    .ok_or_else(|| format!("Failed to find toctree entry '{}'", &child.slug))?,
+                    .get(slug)
+                    .ok_or_else(|| format!("Failed to find toctree entry '{}'", slug))?,
+            };
+
+            let number = match self.section_numbers.get(slug) {
+                Some(number) if self.numbered => format!(
+                    r#"<span class="section-number">{}</span> "#,
+                    number
+                ),
+                _ => "".to_owned(),
             };
 
             result.push(Cow::Owned(format!(
-                r#"<a href="{}">{}</a>"#,
-                current_slug.path_to(child.slug.as_ref(), self.pretty_url),
+                r#"<a href="{}">{}{}</a>"#,
+                self.urls.page_url_from(current_slug, slug),
+                number,
                 title
             )));
-            result.extend(self.generate_html(&child.slug, current_slug, false)?);
+
+            let child_depth = min_depth(
+                maxdepth.map(|depth| depth - 1),
+                child.options.maxdepth.map(|depth| depth - 1),
+            );
+            result.extend(self.generate_html(slug, current_slug, false, child_depth)?);
             result.push(Cow::Borrowed("</li>"));
         }
         result.push(Cow::Borrowed("</ul>"));
@@ -126,3 +550,315 @@ impl TocTree {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slug(s: &str) -> Slug {
+        Slug::new(s.to_owned())
+    }
+
+    fn make_tree(edges: &[(&str, &str)]) -> TocTree {
+        let mut toctree = TocTree::new(slug("index"), UrlConfig::default());
+        let mut titles = HashMap::new();
+        titles.insert(slug("index"), "Index".to_owned());
+        for &(parent, child) in edges {
+            let target = TocTreeTarget::Page(slug(child));
+            let element = TocTreeElement::new(target, None, TocTreeOptions::default());
+            toctree.add(&slug(parent), element);
+            titles.insert(slug(child), child.to_uppercase());
+        }
+
+        toctree.finish(&titles);
+        toctree
+    }
+
+    #[test]
+    fn test_reading_order() {
+        let toctree = make_tree(&[
+            ("index", "tutorials"),
+            ("index", "reference"),
+            ("tutorials", "tutorials/install"),
+            ("tutorials", "tutorials/first"),
+            ("reference", "tutorials/install"),
+        ]);
+
+        assert_eq!(
+            toctree.reading_order,
+            vec![
+                slug("index"),
+                slug("tutorials"),
+                slug("tutorials/install"),
+                slug("tutorials/first"),
+                slug("reference"),
+            ]
+        );
+        assert_eq!(toctree.previous(&slug("index")), None);
+        assert_eq!(toctree.next(&slug("index")), Some(&slug("tutorials")));
+        assert_eq!(
+            toctree.previous(&slug("reference")),
+            Some(&slug("tutorials/first"))
+        );
+        assert_eq!(toctree.next(&slug("reference")), None);
+        assert_eq!(toctree.next(&slug("orphan")), None);
+    }
+
+    #[test]
+    fn test_breadcrumbs() {
+        let toctree = make_tree(&[
+            ("index", "tutorials"),
+            ("index", "reference"),
+            ("reference", "tutorials/install"),
+            ("tutorials", "tutorials/install"),
+        ]);
+
+        assert_eq!(toctree.breadcrumbs(&slug("index")), vec![&slug("index")]);
+        assert_eq!(
+            toctree.breadcrumbs(&slug("tutorials/install")),
+            vec![&slug("index"), &slug("tutorials"), &slug("tutorials/install")]
+        );
+        assert!(toctree.breadcrumbs(&slug("orphan")).is_empty());
+        let install = slug("tutorials/install");
+        assert_eq!(
+            toctree.generate_breadcrumbs_html(&install, &install),
+            concat!(
+                r#"<ol class="breadcrumbs"><li><a href="../../">Index</a></li>"#,
+                r#"<li><a href="../../tutorials">TUTORIALS</a></li>"#,
+                "<li>TUTORIALS/INSTALL</li></ol>"
+            )
+        );
+    }
+
+    #[test]
+    fn test_toctree_options() {
+        let mut toctree = TocTree::new(slug("index"), UrlConfig::default());
+        let mut titles = HashMap::new();
+        for page in &["index", "reference", "reference/b", "reference/a", "reference/a/x"] {
+            titles.insert(slug(page), page.to_uppercase());
+        }
+
+        let hidden = TocTreeOptions {
+            hidden: true,
+            ..TocTreeOptions::default()
+        };
+        let shallow = TocTreeOptions {
+            maxdepth: Some(1),
+            ..TocTreeOptions::default()
+        };
+        let pattern = glob::Pattern::new("reference/*").unwrap();
+
+        toctree.add(
+            &slug("index"),
+            TocTreeElement::new(TocTreeTarget::Page(slug("reference")), None, shallow),
+        );
+        toctree.add(
+            &slug("index"),
+            TocTreeElement::new(
+                TocTreeTarget::External("https://example.com/?a&b".to_owned()),
+                Some("Example".to_owned()),
+                TocTreeOptions::default(),
+            ),
+        );
+        toctree.add(
+            &slug("reference"),
+            TocTreeElement::new(
+                TocTreeTarget::Page(slug("reference/b")),
+                None,
+                TocTreeOptions::default(),
+            ),
+        );
+        toctree.add(
+            &slug("reference"),
+            TocTreeElement::new(TocTreeTarget::Glob(pattern), None, hidden),
+        );
+        toctree.add(
+            &slug("reference/a"),
+            TocTreeElement::new(
+                TocTreeTarget::Page(slug("reference/a/x")),
+                None,
+                TocTreeOptions::default(),
+            ),
+        );
+        toctree.finish(&titles);
+
+        // Globs match a single level, sorted, without repeating explicit entries
+        assert_eq!(
+            toctree.reading_order,
+            vec![
+                slug("index"),
+                slug("reference"),
+                slug("reference/b"),
+                slug("reference/a"),
+                slug("reference/a/x"),
+            ]
+        );
+        assert_eq!(
+            toctree.breadcrumbs(&slug("reference/a")),
+            vec![&slug("index"), &slug("reference"), &slug("reference/a")]
+        );
+
+        // The reference entry's maxdepth stops its children from being rendered here
+        let expected = concat!(
+            r#"<ul><li class="current"><a href="">INDEX</a></li>"#,
+            r#"<li><a href="reference">REFERENCE</a></li>"#,
+            r#"<li class="external"><a href="https://example.com/?a&amp;b">Example</a></li>"#,
+            "</ul>"
+        );
+        let html = toctree
+            .generate_html(&slug("index"), &slug("index"), true, None)
+            .unwrap();
+        assert_eq!(html.concat(), expected);
+
+        // Hidden entries are never rendered, even where no depth limit applies
+        let html = toctree
+            .generate_html(&slug("reference"), &slug("index"), false, None)
+            .unwrap();
+        assert_eq!(html.concat(), r#"<ul><li><a href="reference/b">REFERENCE/B</a></li></ul>"#);
+
+        let html = toctree
+            .generate_html(&slug("index"), &slug("index"), true, Some(0))
+            .unwrap();
+        assert_eq!(html.concat(), "");
+    }
+
+    #[test]
+    fn test_section_numbers() {
+        let mut toctree = make_tree(&[
+            ("index", "a"),
+            ("index", "b"),
+            ("b", "b/x"),
+            ("b", "b/y"),
+            ("a", "b/y"),
+            ("b/y", "b/y/z"),
+        ]);
+
+        let numbers = toctree.section_numbers();
+        assert_eq!(numbers.get(&slug("index")), None);
+        assert_eq!(numbers[&slug("a")], "1");
+        assert_eq!(numbers[&slug("b/y")], "1.1");
+        assert_eq!(numbers[&slug("b/y/z")], "1.1.1");
+        assert_eq!(numbers[&slug("b")], "2");
+        assert_eq!(numbers[&slug("b/x")], "2.1");
+
+        toctree.set_numbered(true);
+        let html = toctree
+            .generate_html(&slug("index"), &slug("index"), true, Some(1))
+            .unwrap();
+        assert_eq!(
+            html.concat(),
+            concat!(
+                r#"<ul><li class="current"><a href="">Index</a></li>"#,
+                r#"<li><a href="a"><span class="section-number">1</span> A</a></li>"#,
+                r#"<li><a href="b"><span class="section-number">2</span> B</a></li></ul>"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_missing_pages() {
+        let mut toctree = TocTree::new(slug("index"), UrlConfig::default());
+        let mut titles = HashMap::new();
+        titles.insert(slug("index"), "Index".to_owned());
+        titles.insert(slug("a"), "A".to_owned());
+        titles.insert(slug("orphan"), "Orphan".to_owned());
+
+        let location = SourceLocation {
+            path: "content/index.rocket".into(),
+            lineno: 3,
+        };
+        for child in &["a", "missing"] {
+            let target = TocTreeTarget::Page(slug(child));
+            let element = TocTreeElement::new(target, None, TocTreeOptions::default());
+            toctree.add(&slug("index"), element.with_location(location.clone()));
+        }
+
+        assert_eq!(
+            toctree.finish(&titles),
+            vec!["Unknown page 'missing' in toctree\n  --> content/index.rocket:3".to_owned()]
+        );
+        assert!(toctree.contains(&slug("a")));
+        assert!(!toctree.contains(&slug("orphan")));
+
+        let html = toctree
+            .generate_html(&slug("index"), &slug("index"), true, None)
+            .unwrap();
+        assert_eq!(
+            html.concat(),
+            r#"<ul><li class="current"><a href="">Index</a></li><li><a href="a">A</a></li></ul>"#
+        );
+    }
+
+    #[test]
+    fn test_missing_root() {
+        let mut toctree = TocTree::new(slug("home"), UrlConfig::default());
+        let target = TocTreeTarget::Page(slug("install"));
+        let element = TocTreeElement::new(target, None, TocTreeOptions::default());
+        toctree.add(&slug("home"), element);
+
+        let mut titles = HashMap::new();
+        titles.insert(slug("index"), "Index".to_owned());
+        titles.insert(slug("install"), "Install".to_owned());
+        assert!(toctree.finish(&titles).is_empty());
+
+        assert_eq!(
+            toctree.check_root("main"),
+            Some("Unknown root page 'home' for toctree 'main'".to_owned())
+        );
+        assert!(!toctree.contains(&slug("install")));
+        let html = toctree.generate_html(&slug("home"), &slug("install"), true, None);
+        assert_eq!(
+            html.map(|parts| parts.concat()),
+            Ok(r#"<ul><li class="current"><a href="../install">Install</a></li></ul>"#.to_owned())
+        );
+
+        let toctree = make_tree(&[("index", "install")]);
+        assert_eq!(toctree.check_root("main"), None);
+    }
+
+    #[test]
+    fn test_reading_order_cycle() {
+        let toctree = make_tree(&[("index", "a"), ("a", "b"), ("b", "a"), ("b", "index")]);
+        assert_eq!(
+            toctree.reading_order,
+            vec![slug("index"), slug("a"), slug("b")]
+        );
+    }
+
+    #[test]
+    fn test_cycles() {
+        let mut toctree = TocTree::new(slug("index"), UrlConfig::default());
+        let mut titles = HashMap::new();
+        titles.insert(slug("index"), "Index".to_owned());
+        let edges = [("index", "a"), ("a", "b"), ("b", "a"), ("c", "d"), ("d", "c")];
+        for (lineno, &(parent, child)) in edges.iter().enumerate() {
+            let location = SourceLocation {
+                path: format!("content/{}.rocket", parent).into(),
+                lineno: lineno as i32,
+            };
+            let target = TocTreeTarget::Page(slug(child));
+            let element = TocTreeElement::new(target, None, TocTreeOptions::default());
+            toctree.add(&slug(parent), element.with_location(location));
+            titles.insert(slug(child), child.to_uppercase());
+        }
+
+        assert_eq!(
+            toctree.finish(&titles),
+            vec![
+                "Cycle in toctree: a -> b -> a\n  --> content/b.rocket:2".to_owned(),
+                "Cycle in toctree: c -> d -> c\n  --> content/d.rocket:4".to_owned(),
+            ]
+        );
+
+        assert!(toctree.is_ancestor_of(&slug("a"), &slug("b")));
+        assert!(!toctree.is_ancestor_of(&slug("b"), &slug("a")));
+        assert!(!toctree.is_ancestor_of(&slug("c"), &slug("a")));
+        assert_eq!(toctree.breadcrumbs(&slug("b")).len(), 3);
+
+        let html = toctree
+            .generate_html(&slug("index"), &slug("b"), true, None)
+            .unwrap()
+            .concat();
+        assert!(html.ends_with(r#"<a href="../b">B</a></li></ul></li></ul>"#));
+    }
+}